@@ -0,0 +1,886 @@
+//! An assembler for Octo, the CHIP-8 assembly language.
+//!
+//! Supports labels, `:const`, `:alias`, `:macro`, `:calc`, `:org`, `:byte`,
+//! `:call`, the structured `if ... then`, `if ... begin ... else ... end`,
+//! `loop ... while ... again` forms, and every instruction up to XO-CHIP.
+//! Programs start at the label `main`; unless `main` is the first thing
+//! in the program a jump to it is placed at 0x200.
+//!
+//! As in Octo, `:calc` expressions are evaluated right to left with no
+//! operator precedence, so `{ 2 * 3 + 1 }` is 8. Use parentheses to group.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+const START: usize = 0x200;
+
+/// Most tokens that macro expansion may produce, to catch runaway recursion.
+const MAX_EXPANSION: usize = 1_000_000;
+
+/// A problem with the source, at the line and column (both from 1) of
+/// the token it was found at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line:    usize,
+    pub column:  usize,
+    pub message: String
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+#[derive(Debug, Clone)]
+struct Token {
+    text:   String,
+    line:   usize,
+    column: usize
+}
+
+/// Splits the source into whitespace separated tokens, dropping `#` comments.
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for (n, line) in source.lines().enumerate() {
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => line
+        };
+        let mut start = None;
+        for (column, c) in line.char_indices().chain(Some((line.len(), ' '))) {
+            match (c.is_whitespace(), start) {
+                (true, Some(from)) => {
+                    tokens.push(Token { text: line[from..column].to_string(), line: n + 1, column: line[..from].chars().count() + 1 });
+                    start = None;
+                },
+                (false, None) => start = Some(column),
+                _ => {}
+            }
+        }
+    }
+    tokens
+}
+
+/// Parses a decimal, `0x` hexadecimal or `0b` binary number, optionally negative.
+fn parse_number(text: &str) -> Option<f64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text)
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()? as f64
+    } else if let Some(binary) = digits.strip_prefix("0b").or_else(|| digits.strip_prefix("0B")) {
+        i64::from_str_radix(binary, 2).ok()? as f64
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+struct Macro {
+    params: Vec<String>,
+    body:   Vec<Token>
+}
+
+/// Where a label's address is to be written once it is known.
+enum Fixup {
+    Low12(usize),   // The low 12 bits of the instruction at this address.
+    Word(usize)     // The 16 bit word at this address.
+}
+
+/// An open structured control flow block.
+enum Flow {
+    If(usize, Token),                   // Address of the jump taken when the condition fails.
+    Else(usize, Token),                 // Address of the jump over the else branch.
+    Loop(usize, Vec<usize>, Token)      // Start of the loop, and the jumps out of its whiles.
+}
+
+struct Assembler {
+    tokens:    Vec<Token>,
+    pos:       usize,
+    rom:       Vec<u8>,         // Bytes from 0x200 on.
+    here:      usize,
+    main_slot: bool,            // Whether 0x200 holds a jump to main, yet to be filled in.
+    labels:    HashMap<String, usize>,
+    constants: HashMap<String, f64>,
+    aliases:   HashMap<String, u8>,
+    macros:    HashMap<String, Macro>,
+    fixups:    Vec<(Fixup, Token)>,
+    flow:      Vec<Flow>
+}
+
+/// Assembles Octo source into a ROM to be loaded at 0x200.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut asm = Assembler {
+        tokens:    tokenize(source),
+        pos:       0,
+        rom:       vec![0, 0],
+        here:      START + 2,
+        main_slot: true,
+        labels:    HashMap::new(),
+        constants: HashMap::new(),
+        aliases:   HashMap::new(),
+        macros:    HashMap::new(),
+        fixups:    Vec::new(),
+        flow:      Vec::new()
+    };
+    asm.run()?;
+    Ok(asm.rom)
+}
+
+impl Assembler {
+    fn run(&mut self) -> Result<(), AsmError> {
+        while self.pos < self.tokens.len() {
+            let token = self.next()?;
+            self.statement(token)?;
+        }
+
+        if let Some(flow) = self.flow.pop() {
+            let (token, message) = match flow {
+                Flow::If(_, token) | Flow::Else(_, token) => (token, "this block is missing an 'end'"),
+                Flow::Loop(_, _, token) => (token, "this loop is missing an 'again'")
+            };
+            return Err(error(&token, message));
+        }
+
+        let main = match self.labels.get("main") {
+            Some(&main) => main,
+            None => return Err(AsmError { line: 1, column: 1, message: "the program has no 'main' label".to_string() })
+        };
+        if self.main_slot {
+            self.write(START, 0x10 | (main >> 8) as u8);
+            self.write(START + 1, main as u8);
+        }
+
+        for (fixup, token) in std::mem::take(&mut self.fixups) {
+            let addr = *self.labels.get(&token.text).ok_or_else(|| error(&token, &format!("undefined name '{}'", token.text)))?;
+            match fixup {
+                Fixup::Low12(at) => {
+                    if addr > 0xFFF {
+                        return Err(error(&token, &format!("address 0x{:X} of '{}' does not fit in 12 bits", addr, token.text)));
+                    }
+                    self.rom[at - START] |= (addr >> 8) as u8;
+                    self.rom[at - START + 1] = addr as u8;
+                },
+                Fixup::Word(at) => {
+                    self.write(at, (addr >> 8) as u8);
+                    self.write(at + 1, addr as u8);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Token, AsmError> {
+        match self.tokens.get(self.pos) {
+            Some(token) => {
+                self.pos += 1;
+                Ok(token.clone())
+            },
+            None => {
+                let last = self.tokens.last().cloned().unwrap_or(Token { text: String::new(), line: 1, column: 1 });
+                Err(error(&last, "unexpected end of file"))
+            }
+        }
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|token| token.text.as_str())
+    }
+
+    fn expect(&mut self, text: &str) -> Result<Token, AsmError> {
+        let token = self.next()?;
+        if token.text != text {
+            return Err(error(&token, &format!("expected '{}', found '{}'", text, token.text)));
+        }
+        Ok(token)
+    }
+
+    fn write(&mut self, addr: usize, byte: u8) {
+        let offset = addr - START;
+        if offset >= self.rom.len() {
+            self.rom.resize(offset + 1, 0);
+        }
+        self.rom[offset] = byte;
+    }
+
+    fn emit(&mut self, token: &Token, byte: u8) -> Result<(), AsmError> {
+        if self.here > 0xFFFF {
+            return Err(error(token, "the program does not fit in 64 KiB"));
+        }
+        let here = self.here;
+        self.write(here, byte);
+        self.here += 1;
+        Ok(())
+    }
+
+    fn inst(&mut self, token: &Token, opcode: u16) -> Result<(), AsmError> {
+        self.emit(token, (opcode >> 8) as u8)?;
+        self.emit(token, opcode as u8)
+    }
+
+    /// Emits `opcode` with the address `target` in its low 12 bits.
+    fn inst_addr(&mut self, token: &Token, opcode: u16, target: &Token) -> Result<(), AsmError> {
+        let here = self.here;
+        match self.address(target)? {
+            Some(addr) if addr > 0xFFF => return Err(error(target, &format!("address 0x{:X} does not fit in 12 bits", addr))),
+            Some(addr) => return self.inst(token, opcode | addr as u16),
+            None => self.fixups.push((Fixup::Low12(here), target.clone()))
+        }
+        self.inst(token, opcode)
+    }
+
+    /// The address `token` names, or None if it is a label not yet defined.
+    fn address(&self, token: &Token) -> Result<Option<usize>, AsmError> {
+        if let Some(&addr) = self.labels.get(&token.text) {
+            return Ok(Some(addr));
+        }
+        if let Some(value) = self.number(token) {
+            if value < 0.0 || value > 0xFFFF as f64 {
+                return Err(error(token, &format!("address {} is out of range", value)));
+            }
+            return Ok(Some(value as usize));
+        }
+        if self.register(token).is_some() || !is_name(&token.text) {
+            return Err(error(token, &format!("expected an address, found '{}'", token.text)));
+        }
+        Ok(None)
+    }
+
+    /// The value of a number or constant token.
+    fn number(&self, token: &Token) -> Option<f64> {
+        parse_number(&token.text).or_else(|| self.constants.get(&token.text).cloned())
+    }
+
+    /// A value that must be known now and fit in `bits` bits. Negative
+    /// values down to -2^(bits-1) are accepted as two's complement.
+    fn value(&self, token: &Token, bits: u32) -> Result<u16, AsmError> {
+        let value = match self.number(token) {
+            Some(value) => value,
+            None => match self.labels.get(&token.text) {
+                Some(&addr) => addr as f64,
+                None => return Err(error(token, &format!("expected a number, found '{}'", token.text)))
+            }
+        };
+        let max = (1u32 << bits) as f64;
+        if value < -max / 2.0 || value >= max {
+            return Err(error(token, &format!("{} does not fit in {} bits", value, bits)));
+        }
+        Ok((value as i64 & (max as i64 - 1)) as u16)
+    }
+
+    fn register(&self, token: &Token) -> Option<u8> {
+        if let Some(&reg) = self.aliases.get(&token.text) {
+            return Some(reg);
+        }
+        let text = token.text.to_lowercase();
+        if text.len() == 2 && text.starts_with('v') {
+            return u8::from_str_radix(&text[1..], 16).ok();
+        }
+        None
+    }
+
+    fn expect_register(&mut self) -> Result<u16, AsmError> {
+        let token = self.next()?;
+        self.register(&token).map(u16::from).ok_or_else(|| error(&token, &format!("expected a register, found '{}'", token.text)))
+    }
+
+    fn statement(&mut self, token: Token) -> Result<(), AsmError> {
+        match token.text.as_str() {
+            ":" => {
+                let name = self.next()?;
+                self.define(&name)?;
+                if name.text == "main" && self.main_slot && self.here == START + 2 && self.rom.len() == 2 {
+                    // main comes first, so no jump to it is needed.
+                    self.rom.clear();
+                    self.here = START;
+                    self.main_slot = false;
+                }
+                self.labels.insert(name.text, self.here);
+            },
+            ":const" => {
+                let name = self.next()?;
+                self.define(&name)?;
+                let value = self.next()?;
+                let value = self.number(&value).or_else(|| self.labels.get(&value.text).map(|&addr| addr as f64))
+                    .ok_or_else(|| error(&value, &format!("expected a number, found '{}'", value.text)))?;
+                self.constants.insert(name.text, value);
+            },
+            ":alias" => {
+                let name = self.next()?;
+                if !is_name(&name.text) {
+                    return Err(error(&name, &format!("'{}' cannot be used as a name", name.text)));
+                }
+                let reg = self.expect_register()? as u8;
+                self.aliases.insert(name.text, reg);
+            },
+            ":calc" => {
+                let name = self.next()?;
+                self.define(&name)?;
+                let value = self.braced_expression()?;
+                self.constants.insert(name.text, value);
+            },
+            ":macro" => {
+                let name = self.next()?;
+                self.define(&name)?;
+                let mut params = Vec::new();
+                while self.peek() != Some("{") {
+                    params.push(self.next()?.text);
+                }
+                self.expect("{")?;
+                let mut body = Vec::new();
+                let mut depth = 0;
+                loop {
+                    let token = self.next()?;
+                    match token.text.as_str() {
+                        "{" => depth += 1,
+                        "}" if depth == 0 => break,
+                        "}" => depth -= 1,
+                        _ => {}
+                    }
+                    body.push(token);
+                }
+                self.macros.insert(name.text, Macro { params, body });
+            },
+            ":org" => {
+                let addr = self.tokens.get(self.pos).cloned().unwrap_or_else(|| token.clone());
+                let value = if self.peek() == Some("{") { self.braced_expression()? } else {
+                    let addr = self.next()?;
+                    self.number(&addr).ok_or_else(|| error(&addr, "expected an address"))?
+                };
+                if value < START as f64 || value > 0xFFFF as f64 {
+                    return Err(error(&addr, &format!("cannot assemble at 0x{:X}", value as i64)));
+                }
+                self.here = value as usize;
+            },
+            ":byte" => {
+                let value = if self.peek() == Some("{") { self.braced_expression()? as i64 as u16 & 0xFF } else {
+                    let value = self.next()?;
+                    self.value(&value, 8)?
+                };
+                self.emit(&token, value as u8)?;
+            },
+            ":call" => {
+                let target = self.next()?;
+                self.inst_addr(&token, 0x2000, &target)?;
+            },
+            ";" | "return" => self.inst(&token, 0x00EE)?,
+            "clear"        => self.inst(&token, 0x00E0)?,
+            "scroll-right" => self.inst(&token, 0x00FB)?,
+            "scroll-left"  => self.inst(&token, 0x00FC)?,
+            "exit"         => self.inst(&token, 0x00FD)?,
+            "lores"        => self.inst(&token, 0x00FE)?,
+            "hires"        => self.inst(&token, 0x00FF)?,
+            "audio"        => self.inst(&token, 0xF002)?,
+            "scroll-down" | "scroll-up" => {
+                let n = self.next()?;
+                let n = self.value(&n, 4)?;
+                self.inst(&token, if token.text == "scroll-down" { 0x00C0 } else { 0x00D0 } | n)?;
+            },
+            "plane" => {
+                let n = self.next()?;
+                let n = self.value(&n, 4)?;
+                self.inst(&token, 0xF001 | n << 8)?;
+            },
+            "bcd"       => { let x = self.expect_register()?; self.inst(&token, 0xF033 | x << 8)?; },
+            "saveflags" => { let x = self.expect_register()?; self.inst(&token, 0xF075 | x << 8)?; },
+            "loadflags" => { let x = self.expect_register()?; self.inst(&token, 0xF085 | x << 8)?; },
+            "save" | "load" => {
+                let x = self.expect_register()?;
+                if self.peek() == Some("-") {
+                    self.next()?;
+                    let y = self.expect_register()?;
+                    self.inst(&token, if token.text == "save" { 0x5002 } else { 0x5003 } | x << 8 | y << 4)?;
+                } else {
+                    self.inst(&token, if token.text == "save" { 0xF055 } else { 0xF065 } | x << 8)?;
+                }
+            },
+            "sprite" => {
+                let x = self.expect_register()?;
+                let y = self.expect_register()?;
+                let n = self.next()?;
+                let n = self.value(&n, 4)?;
+                self.inst(&token, 0xD000 | x << 8 | y << 4 | n)?;
+            },
+            "jump" | "jump0" | "native" => {
+                let target = self.next()?;
+                let opcode = match token.text.as_str() { "jump" => 0x1000, "jump0" => 0xB000, _ => 0x0000 };
+                self.inst_addr(&token, opcode, &target)?;
+            },
+            "i" => self.assign_i(&token)?,
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.expect_register()?;
+                let opcode = match token.text.as_str() { "delay" => 0xF015, "buzzer" => 0xF018, _ => 0xF03A };
+                self.inst(&token, opcode | x << 8)?;
+            },
+            "if" => {
+                self.conditional(false)?;
+                let form = self.next()?;
+                match form.text.as_str() {
+                    "then" => {},
+                    "begin" => {
+                        // The condition was compiled to skip the next
+                        // instruction when false; flip it to skip the
+                        // jump past the block when true instead.
+                        self.flip_skip();
+                        let here = self.here;
+                        self.inst(&token, 0x1000)?;
+                        self.flow.push(Flow::If(here, token));
+                    },
+                    _ => return Err(error(&form, &format!("expected 'then' or 'begin', found '{}'", form.text)))
+                }
+            },
+            "else" => {
+                match self.flow.pop() {
+                    Some(Flow::If(jump, _)) => {
+                        let here = self.here;
+                        self.inst(&token, 0x1000)?;
+                        self.patch_jump(jump, self.here);
+                        self.flow.push(Flow::Else(here, token));
+                    },
+                    _ => return Err(error(&token, "'else' without 'if ... begin'"))
+                }
+            },
+            "end" => {
+                match self.flow.pop() {
+                    Some(Flow::If(jump, _)) | Some(Flow::Else(jump, _)) => self.patch_jump(jump, self.here),
+                    _ => return Err(error(&token, "'end' without 'if ... begin'"))
+                }
+            },
+            "loop" => self.flow.push(Flow::Loop(self.here, Vec::new(), token)),
+            "while" => {
+                self.conditional(true)?;
+                let here = self.here;
+                self.inst(&token, 0x1000)?;
+                match self.flow.iter_mut().rev().find_map(|flow| match *flow { Flow::Loop(_, ref mut exits, _) => Some(exits), _ => None }) {
+                    Some(exits) => exits.push(here),
+                    None => return Err(error(&token, "'while' outside of a loop"))
+                }
+            },
+            "again" => {
+                match self.flow.pop() {
+                    Some(Flow::Loop(start, exits, _)) => {
+                        self.inst(&token, 0x1000 | start as u16)?;
+                        for exit in exits {
+                            self.patch_jump(exit, self.here);
+                        }
+                    },
+                    _ => return Err(error(&token, "'again' without 'loop'"))
+                }
+            },
+            _ if self.register(&token).is_some() => self.assign_register(&token)?,
+            _ if self.macros.contains_key(&token.text) => self.expand(&token)?,
+            _ if self.number(&token).is_some() => {
+                let value = self.value(&token, 8)?;
+                self.emit(&token, value as u8)?;
+            },
+            _ if is_name(&token.text) => {
+                if let Some(":=") | Some("+=") | Some("-=") | Some("|=") | Some("&=") | Some("^=") | Some(">>=") | Some("<<=") | Some("=-") = self.peek() {
+                    return Err(error(&token, &format!("'{}' is not a register", token.text)));
+                }
+                // Any other name is a call to a subroutine.
+                self.inst_addr(&token, 0x2000, &token)?
+            },
+            _ => return Err(error(&token, &format!("unexpected '{}'", token.text)))
+        }
+        Ok(())
+    }
+
+    /// Checks that `name` can be given a new meaning.
+    fn define(&self, name: &Token) -> Result<(), AsmError> {
+        if !is_name(&name.text) || self.register(name).is_some() {
+            return Err(error(name, &format!("'{}' cannot be used as a name", name.text)));
+        }
+        if self.labels.contains_key(&name.text) || self.constants.contains_key(&name.text) || self.macros.contains_key(&name.text) {
+            return Err(error(name, &format!("'{}' is already defined", name.text)));
+        }
+        Ok(())
+    }
+
+    fn expand(&mut self, token: &Token) -> Result<(), AsmError> {
+        let mut args = HashMap::new();
+        let params = self.macros[&token.text].params.clone();
+        for param in params {
+            let arg = self.next()?;
+            args.insert(param, arg.text);
+        }
+        let body: Vec<Token> = self.macros[&token.text].body.iter().map(|body| {
+            match args.get(&body.text) {
+                Some(arg) => Token { text: arg.clone(), ..body.clone() },
+                None => body.clone()
+            }
+        }).collect();
+
+        if self.tokens.len() + body.len() > MAX_EXPANSION {
+            return Err(error(token, "macro expansion is too deep"));
+        }
+        let pos = self.pos;
+        self.tokens.splice(pos..pos, body);
+        Ok(())
+    }
+
+    fn assign_i(&mut self, token: &Token) -> Result<(), AsmError> {
+        let op = self.next()?;
+        match op.text.as_str() {
+            "+=" => {
+                let x = self.expect_register()?;
+                self.inst(token, 0xF01E | x << 8)
+            },
+            ":=" => {
+                let source = self.next()?;
+                match source.text.as_str() {
+                    "hex" => { let x = self.expect_register()?; self.inst(token, 0xF029 | x << 8) },
+                    "bighex" => { let x = self.expect_register()?; self.inst(token, 0xF030 | x << 8) },
+                    "long" => {
+                        let target = self.next()?;
+                        self.inst(token, 0xF000)?;
+                        let here = self.here;
+                        let addr = match self.address(&target)? {
+                            Some(addr) => addr,
+                            None => {
+                                self.fixups.push((Fixup::Word(here), target.clone()));
+                                0
+                            }
+                        };
+                        self.inst(token, addr as u16)
+                    },
+                    _ => self.inst_addr(token, 0xA000, &source)
+                }
+            },
+            _ => Err(error(&op, &format!("expected ':=' or '+=', found '{}'", op.text)))
+        }
+    }
+
+    fn assign_register(&mut self, token: &Token) -> Result<(), AsmError> {
+        let x = self.register(token).unwrap() as u16;
+        let op = self.next()?;
+        let source = self.next()?;
+
+        if let Some(y) = self.register(&source) {
+            let y = y as u16;
+            let low = match op.text.as_str() {
+                ":=" => 0x0, "|=" => 0x1, "&=" => 0x2, "^=" => 0x3, "+=" => 0x4,
+                "-=" => 0x5, ">>=" => 0x6, "=-" => 0x7, "<<=" => 0xE,
+                _ => return Err(error(&op, &format!("unknown operator '{}'", op.text)))
+            };
+            return self.inst(token, 0x8000 | x << 8 | y << 4 | low);
+        }
+
+        match (op.text.as_str(), source.text.as_str()) {
+            (":=", "key") => self.inst(token, 0xF00A | x << 8),
+            (":=", "delay") => self.inst(token, 0xF007 | x << 8),
+            (":=", "random") => {
+                let mask = self.next()?;
+                let mask = self.value(&mask, 8)?;
+                self.inst(token, 0xC000 | x << 8 | mask)
+            },
+            (":=", _) => {
+                let value = self.value(&source, 8)?;
+                self.inst(token, 0x6000 | x << 8 | value)
+            },
+            ("+=", _) => {
+                let value = self.value(&source, 8)?;
+                self.inst(token, 0x7000 | x << 8 | value)
+            },
+            ("-=", _) => {
+                let value = self.value(&source, 8)?;
+                self.inst(token, 0x7000 | x << 8 | (0x100 - value) & 0xFF)
+            },
+            _ => Err(error(&op, &format!("unknown operator '{}' for a constant", op.text)))
+        }
+    }
+
+    /// Compiles a condition so that the instruction after it is skipped
+    /// when the condition is false, or when true if `negated`.
+    ///
+    /// Comparisons other than equality subtract into vf and skip on the
+    /// borrow flag.
+    fn conditional(&mut self, negated: bool) -> Result<(), AsmError> {
+        let x = self.expect_register()?;
+        let op = self.next()?;
+        let cmp = if negated {
+            match op.text.as_str() {
+                "==" => "!=", "!=" => "==", "key" => "-key", "-key" => "key",
+                "<" => ">=", ">=" => "<", ">" => "<=", "<=" => ">",
+                _ => ""
+            }
+        } else {
+            op.text.as_str()
+        }.to_string();
+
+        match cmp.as_str() {
+            "key" => return self.inst(&op, 0xE0A1 | x << 8),
+            "-key" => return self.inst(&op, 0xE09E | x << 8),
+            "==" | "!=" | "<" | ">" | "<=" | ">=" => {},
+            _ => return Err(error(&op, &format!("unknown comparison '{}'", op.text)))
+        }
+
+        let rhs = self.next()?;
+        let y = self.register(&rhs).map(u16::from);
+        let nn = match y { Some(_) => 0, None => self.value(&rhs, 8)? };
+
+        match (cmp.as_str(), y) {
+            ("==", Some(y)) => self.inst(&op, 0x9000 | x << 8 | y << 4),
+            ("==", None)    => self.inst(&op, 0x4000 | x << 8 | nn),
+            ("!=", Some(y)) => self.inst(&op, 0x5000 | x << 8 | y << 4),
+            ("!=", None)    => self.inst(&op, 0x3000 | x << 8 | nn),
+            _ => {
+                // vf is set when a >= b.
+                let (a_is_x, skip_on_flag) = match cmp.as_str() {
+                    "<"  => (true, true),
+                    ">=" => (true, false),
+                    ">"  => (false, true),
+                    _    => (false, false)
+                };
+                match (a_is_x, y) {
+                    (true, Some(y))  => { self.inst(&op, 0x8F00 | x << 4)?; self.inst(&op, 0x8F05 | y << 4)?; },
+                    (true, None)     => { self.inst(&op, 0x6F00 | nn)?; self.inst(&op, 0x8F07 | x << 4)?; },
+                    (false, Some(y)) => { self.inst(&op, 0x8F00 | y << 4)?; self.inst(&op, 0x8F05 | x << 4)?; },
+                    (false, None)    => { self.inst(&op, 0x6F00 | nn)?; self.inst(&op, 0x8F05 | x << 4)?; }
+                }
+                // Skip when the condition is false.
+                self.inst(&op, if skip_on_flag { 0x4F00 } else { 0x3F00 })
+            }
+        }
+    }
+
+    /// Inverts the skip instruction just emitted.
+    fn flip_skip(&mut self) {
+        let at = self.here - 2 - START;
+        let opcode = (self.rom[at] as u16) << 8 | self.rom[at + 1] as u16;
+        let flipped = match opcode & 0xF000 {
+            0x3000 | 0x4000 => opcode ^ 0x7000,     // SE <-> SNE
+            0x5000 | 0x9000 => opcode ^ 0xC000,     // SE <-> SNE on registers
+            _ => opcode ^ 0x003F                    // SKP <-> SKNP
+        };
+        self.rom[at] = (flipped >> 8) as u8;
+        self.rom[at + 1] = flipped as u8;
+    }
+
+    fn patch_jump(&mut self, at: usize, target: usize) {
+        self.write(at, 0x10 | (target >> 8) as u8);
+        self.write(at + 1, target as u8);
+    }
+
+    /// Evaluates `{ expression }`.
+    fn braced_expression(&mut self) -> Result<f64, AsmError> {
+        let open = self.expect("{")?;
+        let mut tokens = Vec::new();
+        let mut depth = 0;
+        loop {
+            let token = self.next()?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" if depth == 0 => break,
+                "}" => depth -= 1,
+                _ => {}
+            }
+            tokens.push(token);
+        }
+        let mut pos = 0;
+        let value = self.expression(&tokens, &mut pos, &open)?;
+        match tokens.get(pos) {
+            Some(token) => Err(error(token, &format!("unexpected '{}' in expression", token.text))),
+            None => Ok(value)
+        }
+    }
+
+    /// Evaluates `term [operator expression]`, so that operators apply
+    /// right to left.
+    fn expression(&self, tokens: &[Token], pos: &mut usize, open: &Token) -> Result<f64, AsmError> {
+        let lhs = self.term(tokens, pos, open)?;
+        let op = match tokens.get(*pos) {
+            Some(op) if op.text != ")" => op.clone(),
+            _ => return Ok(lhs)
+        };
+        *pos += 1;
+        let rhs = self.expression(tokens, pos, open)?;
+        let (a, b) = (lhs as i64, rhs as i64);
+        Ok(match op.text.as_str() {
+            "+"  => lhs + rhs,
+            "-"  => lhs - rhs,
+            "*"  => lhs * rhs,
+            "/"  if rhs == 0.0 => return Err(error(&op, "division by zero")),
+            "/"  => lhs / rhs,
+            "%"  if b == 0 => return Err(error(&op, "division by zero")),
+            "%"  => (a % b) as f64,
+            "&"  => (a & b) as f64,
+            "|"  => (a | b) as f64,
+            "^"  => (a ^ b) as f64,
+            "<<" => (a << (b & 63)) as f64,
+            ">>" => (a >> (b & 63)) as f64,
+            "min" => lhs.min(rhs),
+            "max" => lhs.max(rhs),
+            "<"  => (lhs < rhs) as i64 as f64,
+            ">"  => (lhs > rhs) as i64 as f64,
+            "<=" => (lhs <= rhs) as i64 as f64,
+            ">=" => (lhs >= rhs) as i64 as f64,
+            "==" => (lhs == rhs) as i64 as f64,
+            "!=" => (lhs != rhs) as i64 as f64,
+            _ => return Err(error(&op, &format!("unknown operator '{}'", op.text)))
+        })
+    }
+
+    fn term(&self, tokens: &[Token], pos: &mut usize, open: &Token) -> Result<f64, AsmError> {
+        let token = tokens.get(*pos).ok_or_else(|| error(open, "incomplete expression"))?;
+        *pos += 1;
+        match token.text.as_str() {
+            "(" => {
+                let value = self.expression(tokens, pos, open)?;
+                match tokens.get(*pos) {
+                    Some(close) if close.text == ")" => { *pos += 1; Ok(value) },
+                    _ => Err(error(token, "missing ')'"))
+                }
+            },
+            "-" => Ok(-self.term(tokens, pos, open)?),
+            "~" => Ok(!(self.term(tokens, pos, open)? as i64) as f64),
+            "!" => Ok((self.term(tokens, pos, open)? == 0.0) as i64 as f64),
+            "floor" => Ok(self.term(tokens, pos, open)?.floor()),
+            "HERE" => Ok(self.here as f64),
+            _ => self.number(token)
+                .or_else(|| self.labels.get(&token.text).map(|&addr| addr as f64))
+                .ok_or_else(|| error(token, &format!("unknown value '{}' in expression", token.text)))
+        }
+    }
+}
+
+fn is_name(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+fn error(token: &Token, message: &str) -> AsmError {
+    AsmError { line: token.line, column: token.column, message: message.to_string() }
+}
+
+#[test]
+fn test_assemble_instructions() {
+    let rom = assemble("
+        : main
+            clear
+            v0 := 5  v1 := 0x1F  va += 1  vb -= 2
+            v2 := v3  v2 |= v3  v2 &= v3  v2 ^= v3  v2 += v3
+            v2 -= v3  v2 >>= v3  v2 =- v3  v2 <<= v3
+            i := 0x300  i += v4  i := hex v5
+            v6 := random 0x0F  v7 := key  v8 := delay
+            delay := v9  buzzer := v9
+            sprite v0 v1 5  bcd v2  save v3  load v3
+            jump0 0x204
+            return
+    ").unwrap();
+    assert_eq!(rom, vec![
+        0x00, 0xE0, 0x60, 0x05, 0x61, 0x1F, 0x7A, 0x01, 0x7B, 0xFE,
+        0x82, 0x30, 0x82, 0x31, 0x82, 0x32, 0x82, 0x33, 0x82, 0x34,
+        0x82, 0x35, 0x82, 0x36, 0x82, 0x37, 0x82, 0x3E,
+        0xA3, 0x00, 0xF4, 0x1E, 0xF5, 0x29,
+        0xC6, 0x0F, 0xF7, 0x0A, 0xF8, 0x07,
+        0xF9, 0x15, 0xF9, 0x18,
+        0xD0, 0x15, 0xF2, 0x33, 0xF3, 0x55, 0xF3, 0x65,
+        0xB2, 0x04,
+        0x00, 0xEE
+    ]);
+}
+
+#[test]
+fn test_assemble_labels() {
+    let rom = assemble("
+        : sprite-data  0xF0 0x90
+        : main
+            i := sprite-data
+            draw
+            jump main
+        : draw  sprite v0 v0 2 ;
+    ").unwrap();
+    assert_eq!(rom, vec![
+        0x12, 0x04,             // 200: jump main
+        0xF0, 0x90,             // 202: sprite-data
+        0xA2, 0x02,             // 204: main
+        0x22, 0x0A,             // 206: call draw
+        0x12, 0x04,             // 208
+        0xD0, 0x02, 0x00, 0xEE  // 20A: draw
+    ]);
+}
+
+#[test]
+fn test_assemble_directives() {
+    let rom = assemble("
+        :const SPEED 3
+        :alias x v4
+        :calc DOUBLE { SPEED * 2 + 1 }
+        :macro move reg amount { reg += amount }
+        : main
+            x := SPEED
+            move x DOUBLE
+            :byte { 1 << 4 }
+        :org 0x210
+            :call main
+    ").unwrap();
+    assert_eq!(rom, vec![
+        0x64, 0x03, 0x74, 0x09, 0x10, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x22, 0x00
+    ]);
+}
+
+#[test]
+fn test_assemble_control_flow() {
+    let rom = assemble("
+        : main
+            if v0 == 1 then v1 := 2
+            if v0 != v2 begin
+                v1 := 3
+            else
+                v1 := 4
+            end
+            loop
+                v0 += 1
+                while v0 key
+            again
+            if v0 > 5 then clear
+    ").unwrap();
+    assert_eq!(rom, vec![
+        0x40, 0x01, 0x61, 0x02,             // 200: if v0 == 1 then
+        0x90, 0x20, 0x12, 0x0C,             // 204: if v0 != v2 begin
+        0x61, 0x03, 0x12, 0x0E,             // 208: else
+        0x61, 0x04,                         // 20C: end
+        0x70, 0x01,                         // 20E: loop
+        0xE0, 0x9E, 0x12, 0x16,             // 210: while v0 key
+        0x12, 0x0E,                         // 214: again
+        0x6F, 0x05, 0x8F, 0x05, 0x4F, 0x00, // 216: if v0 > 5 then
+        0x00, 0xE0
+    ]);
+}
+
+#[test]
+fn test_assemble_long_load() {
+    let rom = assemble(": main i := long data  plane 3  : data 0xAA").unwrap();
+    assert_eq!(rom, vec![0xF0, 0x00, 0x02, 0x06, 0xF3, 0x01, 0xAA]);
+}
+
+#[test]
+fn test_assemble_errors() {
+    let err = assemble(": main\n  v0 := 5\n  v0 @= 1\n").unwrap_err();
+    assert_eq!((err.line, err.column), (3, 6));
+    assert_eq!(err.to_string(), "line 3, column 6: unknown operator '@=' for a constant");
+
+    let err = assemble(": main\n  vg := 1\n").unwrap_err();
+    assert_eq!(err.to_string(), "line 2, column 3: 'vg' is not a register");
+
+    let err = assemble(": main\n  jump nowhere\n").unwrap_err();
+    assert_eq!(err.to_string(), "line 2, column 8: undefined name 'nowhere'");
+
+    let err = assemble(": main\n  v0 := 300\n").unwrap_err();
+    assert_eq!((err.line, err.column), (2, 9));
+
+    let err = assemble(": main\n  loop\n  v0 += 1\n").unwrap_err();
+    assert_eq!(err.to_string(), "line 2, column 3: this loop is missing an 'again'");
+
+    assert!(assemble("clear").is_err(), "No main.");
+}
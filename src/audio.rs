@@ -0,0 +1,109 @@
+use std::f32::consts::PI;
+use std::str::FromStr;
+
+/// The shape of the tone played while the sound timer is non-zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Waveform {
+    #[default]
+    Square,
+    Triangle,
+    Sawtooth,
+    Sine,
+}
+
+impl FromStr for Waveform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Waveform, String> {
+        match s {
+            "square"   => Ok(Waveform::Square),
+            "triangle" => Ok(Waveform::Triangle),
+            "sawtooth" => Ok(Waveform::Sawtooth),
+            "sine"     => Ok(Waveform::Sine),
+            _ => Err(format!("Unknown waveform: {}", s))
+        }
+    }
+}
+
+/// The buzzer's tone. A frontend asks it for samples while the sound
+/// timer is sounding, as reported by `Chip8::take_sound_events`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tone {
+    pub frequency: f32,     // In Hz.
+    pub volume:    f32,     // From 0.0 (silent) to 1.0.
+    pub waveform:  Waveform
+}
+
+/// A quiet 440 Hz square wave.
+impl Default for Tone {
+    fn default() -> Tone {
+        Tone { frequency: 440.0, volume: 0.25, waveform: Waveform::Square }
+    }
+}
+
+impl Tone {
+    /// The sample at `phase`, the position through one cycle from 0.0 up
+    /// to 1.0, scaled by the volume.
+    pub fn sample(&self, phase: f32) -> f32 {
+        let phase = phase.fract();
+        let value = match self.waveform {
+            Waveform::Square   => if phase < 0.5 { 1.0 } else { -1.0 },
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Waveform::Sawtooth => 2.0 * phase - 1.0,
+            Waveform::Sine     => (2.0 * PI * phase).sin()
+        };
+        value * self.volume
+    }
+}
+
+/// An XO-CHIP audio pattern: 128 one bit samples, most significant bit
+/// first, looped at a rate set by the pitch register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pattern {
+    pub bits:  [u8; 16],
+    pub pitch: u8
+}
+
+impl Pattern {
+    /// Samples played per second: 4000 at pitch 64, doubling every 48.
+    pub fn rate(&self) -> f32 {
+        4000.0 * 2f32.powf((f32::from(self.pitch) - 64.0) / 48.0)
+    }
+
+    /// The sample `position` samples into the pattern, wrapping around
+    /// at the end, at `volume`.
+    pub fn sample(&self, position: f32, volume: f32) -> f32 {
+        let bit = position as usize % 128;
+        if self.bits[bit / 8] & (0x80 >> (bit % 8)) != 0 { volume } else { -volume }
+    }
+}
+
+#[test]
+fn test_pattern() {
+    let mut pattern = Pattern { bits: [0; 16], pitch: 64 };
+    pattern.bits[0] = 0x80;
+    pattern.bits[15] = 0x01;
+    assert_eq!(pattern.rate(), 4000.0);
+    assert_eq!((pattern.sample(0.5, 0.5), pattern.sample(1.0, 0.5)), (0.5, -0.5));
+    assert_eq!((pattern.sample(127.0, 0.5), pattern.sample(128.0, 0.5)), (0.5, 0.5), "Loops.");
+
+    pattern.pitch = 112;
+    assert_eq!(pattern.rate(), 8000.0);
+}
+
+#[test]
+fn test_tone_sample() {
+    let mut tone = Tone { frequency: 440.0, volume: 0.5, waveform: Waveform::Square };
+    assert_eq!(tone.sample(0.25), 0.5);
+    assert_eq!(tone.sample(0.75), -0.5);
+
+    tone.waveform = Waveform::Triangle;
+    assert_eq!(tone.sample(0.0), -0.5);
+    assert_eq!(tone.sample(0.5), 0.5);
+
+    tone.waveform = Waveform::Sawtooth;
+    assert_eq!(tone.sample(0.5), 0.0);
+
+    tone.waveform = Waveform::Sine;
+    assert!((tone.sample(0.25) - 0.5).abs() < 1e-6);
+}
@@ -0,0 +1,247 @@
+//! Recordings of the screen, as an animated GIF or as numbered PNG frames.
+//!
+//! A CHIP-8 screen changes rarely, so a frame is only written when the
+//! screen differs from the last one, and is shown for as long as it
+//! stayed the same. Every frame is drawn in the same four colour palette.
+//! GIF frames only cover the rectangle that changed.
+//!
+//! Frames are drawn on a canvas the size of the hi-res display, so a
+//! recording can span a switch between resolutions; a lo-res pixel is two
+//! canvas pixels across.
+
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+use machine::Chip8;
+use palette::Palette;
+use scheduler::TIMER_HZ;
+
+/// The canvas size, in hi-res pixels.
+const CANVAS: (usize, usize) = (128, 64);
+
+/// The manifest written alongside PNG frames.
+const MANIFEST: &str = "frames.ffconcat";
+
+/// A recording in progress.
+pub struct Capture {
+    sink: Sink,
+    palette: Palette,
+    scale: usize,                   // Image pixels per hi-res pixel.
+    frame: u32,                     // Frames captured so far.
+    pending: Option<(u32, Vec<u8>)>, // The latest screen and the frame it appeared on, not yet written.
+    written: Option<Vec<u8>>        // The last screen written.
+}
+
+enum Sink {
+    Gif(gif::Encoder<BufWriter<File>>),
+    Frames {
+        dir: PathBuf,
+        manifest: BufWriter<File>,
+        count: u32
+    }
+}
+
+impl Capture {
+    /// Starts recording to `path`: an animated GIF if it ends in `.gif`,
+    /// otherwise a directory of PNG frames with an ffmpeg concat manifest.
+    /// Each hi-res pixel is drawn as a `scale` by `scale` square.
+    pub fn create(path: &str, palette: &Palette, scale: u32) -> io::Result<Capture> {
+        let scale = scale.max(1) as usize;
+        let sink = if path.ends_with(".gif") {
+            // GIF sizes are 16 bit.
+            let size = match (u16::try_from(CANVAS.0 * scale), u16::try_from(CANVAS.1 * scale)) {
+                (Ok(width), Ok(height)) => (width, height),
+                _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("scale {} is too large for a GIF", scale)))
+            };
+            let mut encoder = gif::Encoder::new(BufWriter::new(File::create(path)?), size.0, size.1, &palette_rgb(palette)).map_err(gif_error)?;
+            encoder.set_repeat(gif::Repeat::Infinite).map_err(gif_error)?;
+            Sink::Gif(encoder)
+        } else {
+            let dir = PathBuf::from(path);
+            fs::create_dir_all(&dir)?;
+            let mut manifest = BufWriter::new(File::create(dir.join(MANIFEST))?);
+            writeln!(manifest, "ffconcat version 1.0")?;
+            Sink::Frames { dir, manifest, count: 0 }
+        };
+        Ok(Capture {
+            sink,
+            palette: *palette,
+            scale,
+            frame: 0,
+            pending: None,
+            written: None
+        })
+    }
+
+    /// Captures the screen for one 60 Hz frame.
+    pub fn capture(&mut self, c8: &Chip8) -> io::Result<()> {
+        let screen = self.canvas(c8);
+        if self.pending.as_ref().is_none_or(|(_, pending)| *pending != screen) {
+            if let Some((start, pending)) = self.pending.take() {
+                self.write(start, pending)?;
+            }
+            self.pending = Some((self.frame, screen));
+        }
+        self.frame += 1;
+        Ok(())
+    }
+
+    /// Writes the last screen and closes the recording. Returns the
+    /// number of frames captured.
+    pub fn finish(mut self) -> io::Result<u32> {
+        if let Some((start, pending)) = self.pending.take() {
+            self.write(start, pending)?;
+        }
+        match self.sink {
+            Sink::Gif(encoder) => {
+                encoder.into_inner()?.flush()?;
+            },
+            Sink::Frames { mut manifest, count, .. } => {
+                // ffmpeg ignores the last file's duration unless it is
+                // listed again.
+                if count > 0 {
+                    writeln!(manifest, "file '{}'", frame_name(count - 1))?;
+                }
+                manifest.flush()?;
+            }
+        }
+        Ok(self.frame)
+    }
+
+    /// Draws `gfx` on the canvas as palette indices.
+    fn canvas(&self, c8: &Chip8) -> Vec<u8> {
+        let (width, height) = c8.display_size();
+        let (across, down) = (CANVAS.0 * self.scale / width, CANVAS.1 * self.scale / height);
+        let mut canvas = Vec::with_capacity(CANVAS.0 * CANVAS.1 * self.scale * self.scale);
+        for y in 0..CANVAS.1 * self.scale {
+            let row = &c8.gfx[y / down * width..][..width];
+            canvas.extend((0..CANVAS.0 * self.scale).map(|x| row[x / across] & 0x3));
+        }
+        canvas
+    }
+
+    /// Writes `screen`, which appeared on frame `start` and lasted until
+    /// the current frame.
+    fn write(&mut self, start: u32, screen: Vec<u8>) -> io::Result<()> {
+        let (width, height) = (CANVAS.0 * self.scale, CANVAS.1 * self.scale);
+        match self.sink {
+            Sink::Gif(ref mut encoder) => {
+                // GIF delays are in hundredths of a second. Rounding the
+                // start and end rather than the length keeps the total right.
+                let centis = |frame: u32| u64::from(frame) * 100 / u64::from(TIMER_HZ);
+                let delay = (centis(self.frame) - centis(start)).min(u64::from(u16::MAX)) as u16;
+                let (left, top, right, bottom) = match self.written {
+                    Some(ref written) => changed(written, &screen, width).unwrap_or((0, 0, 1, 1)),
+                    None => (0, 0, width, height)
+                };
+                let mut buffer = Vec::with_capacity((right - left) * (bottom - top));
+                for y in top..bottom {
+                    buffer.extend_from_slice(&screen[y * width + left..y * width + right]);
+                }
+                encoder.write_frame(&gif::Frame {
+                    delay,
+                    left: left as u16,
+                    top: top as u16,
+                    width: (right - left) as u16,
+                    height: (bottom - top) as u16,
+                    buffer: buffer.into(),
+                    ..gif::Frame::default()
+                }).map_err(gif_error)?;
+            },
+            Sink::Frames { ref dir, ref mut manifest, ref mut count } => {
+                let name = frame_name(*count);
+                let mut encoder = png::Encoder::new(BufWriter::new(File::create(dir.join(&name))?), width as u32, height as u32);
+                encoder.set_color(png::ColorType::Indexed);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder.set_palette(palette_rgb(&self.palette));
+                let mut writer = encoder.write_header()?;
+                writer.write_image_data(&screen)?;
+                writer.finish()?;
+
+                let seconds = f64::from(self.frame - start) / f64::from(TIMER_HZ);
+                writeln!(manifest, "file '{}'", name)?;
+                writeln!(manifest, "duration {:.6}", seconds)?;
+                *count += 1;
+            }
+        }
+        self.written = Some(screen);
+        Ok(())
+    }
+}
+
+/// The palette as packed RGB triples.
+fn palette_rgb(palette: &Palette) -> Vec<u8> {
+    palette.colours.iter().flat_map(|&(r, g, b)| vec![r, g, b]).collect()
+}
+
+fn gif_error(err: gif::EncodingError) -> io::Error {
+    match err {
+        gif::EncodingError::Io(err) => err,
+        err => io::Error::new(io::ErrorKind::InvalidInput, err)
+    }
+}
+
+fn frame_name(n: u32) -> String {
+    format!("frame{:06}.png", n)
+}
+
+/// The smallest rectangle, as left, top, right and bottom, holding every
+/// pixel that differs between two canvases, if any do.
+fn changed(before: &[u8], after: &[u8], width: usize) -> Option<(usize, usize, usize, usize)> {
+    let mut bounds: Option<(usize, usize, usize, usize)> = None;
+    for (i, _) in before.iter().zip(after).enumerate().filter(|&(_, (a, b))| a != b) {
+        let (x, y) = (i % width, i / width);
+        bounds = Some(match bounds {
+            Some((left, top, right, bottom)) => (left.min(x), top.min(y), right.max(x + 1), bottom.max(y + 1)),
+            None => (x, y, x + 1, y + 1)
+        });
+    }
+    bounds
+}
+
+#[test]
+fn test_changed() {
+    let before = [0, 0, 0, 0,
+                  0, 0, 0, 0,
+                  0, 0, 0, 0];
+    let after  = [0, 0, 0, 0,
+                  0, 1, 0, 0,
+                  0, 0, 1, 0];
+    assert_eq!(changed(&before, &after, 4), Some((1, 1, 3, 3)));
+    assert_eq!(changed(&before, &before, 4), None);
+}
+
+#[test]
+fn test_capture_frames() {
+    use std::env;
+    use platform::Platform;
+
+    let dir = env::temp_dir().join(format!("chip8-capture-{}", std::process::id()));
+    let mut c8 = Chip8::new(Platform::CosmacVip);
+    let mut capture = Capture::create(dir.to_str().unwrap(), &Palette::default(), 1).unwrap();
+    for frame in 0..90 {
+        // The screen changes once, half a second in.
+        if frame == 30 {
+            c8.gfx[0] = 1;
+        }
+        capture.capture(&c8).unwrap();
+    }
+    assert_eq!(capture.finish().unwrap(), 90);
+
+    let manifest = fs::read_to_string(dir.join(MANIFEST)).unwrap();
+    assert_eq!(manifest, "ffconcat version 1.0\n\
+                          file 'frame000000.png'\nduration 0.500000\n\
+                          file 'frame000001.png'\nduration 1.000000\n\
+                          file 'frame000001.png'\n");
+    assert!(dir.join("frame000001.png").exists());
+    assert!(!dir.join("frame000002.png").exists());
+    fs::remove_dir_all(&dir).unwrap();
+
+    let gif = env::temp_dir().join(format!("chip8-capture-{}.gif", std::process::id()));
+    assert!(Capture::create(gif.to_str().unwrap(), &Palette::default(), 1024).is_err(), "Too wide for a GIF.");
+    assert!(!gif.exists(), "Nothing written.");
+}
@@ -0,0 +1,312 @@
+//! An interactive debugger, driven by lines of text.
+//!
+//! The frontend feeds it commands and runs the machine a frame at a time
+//! through `run_frame`, which stops at breakpoints and watchpoints. While
+//! paused the frontend carries on drawing and the debugger executes
+//! nothing but what `step` asks for.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use error::Chip8Error;
+use instruction::Instruction;
+use machine::{Chip8, StepOutcome};
+use scheduler::Scheduler;
+
+const HELP: &str = "\
+break ADDR        stop before executing ADDR (no ADDR lists breakpoints)
+delete ADDR       remove the breakpoint at ADDR
+watch ADDR [LEN]  stop after an instruction writes to ADDR..ADDR+LEN
+unwatch ADDR      remove the watchpoint starting at ADDR
+step [N]          execute N instructions (default 1)
+continue          run until a breakpoint or watchpoint
+regs              show the registers and timers
+mem ADDR LEN      dump LEN bytes of memory
+stack             show the call stack
+set REG VALUE     set v0-vf, i, pc, dt or st
+disasm [ADDR] [N] disassemble N instructions (default 8) from ADDR or pc
+Numbers are decimal, or hexadecimal with a 0x prefix.";
+
+/// Parses a decimal or `0x` prefixed hexadecimal number.
+fn parse_number(text: &str) -> Result<usize, String> {
+    let parsed = if text.starts_with("0x") || text.starts_with("0X") {
+        usize::from_str_radix(&text[2..], 16)
+    } else {
+        text.parse()
+    };
+    parsed.map_err(|_| format!("Invalid number: {}", text))
+}
+
+/// Disassembles `count` instructions from `addr`, one per line, marking
+/// the one at the program counter.
+pub fn disassemble(c8: &Chip8, addr: usize, count: usize) -> String {
+    let mut out = String::new();
+    for addr in (addr..).step_by(2).take(count) {
+        if addr + 1 >= c8.memory.len() {
+            break;
+        }
+        let opcode = (c8.memory[addr] as u16) << 8 | c8.memory[addr + 1] as u16;
+        let marker = if addr == c8.pc as usize { "=>" } else { "  " };
+        let _ = match Instruction::decode(opcode) {
+            Some(ins) => writeln!(out, "{} {:03X}: {:04X}  {}", marker, addr, opcode, ins),
+            None => writeln!(out, "{} {:03X}: {:04X}  ???", marker, addr, opcode)
+        };
+    }
+    out
+}
+
+/// Breakpoints, watchpoints and whether the machine is paused.
+#[derive(Debug, Default)]
+pub struct Debugger {
+    pub paused:  bool,
+    breakpoints: BTreeSet<u16>,
+    watchpoints: Vec<(usize, usize)>,   // Start and length of each watched range.
+    resume_at:   Option<u16>            // A breakpoint to run past once, after continuing from it.
+}
+
+impl Debugger {
+    /// Constructs a debugger, paused so breakpoints can be set before
+    /// the program starts.
+    pub fn new() -> Debugger {
+        Debugger { paused: true, ..Debugger::default() }
+    }
+
+    /// Runs a frame through `scheduler` unless paused, pausing at any
+    /// breakpoint or watchpoint hit. Returns how the frame ended, which is
+    /// `Continue` if it was paused or cut short, and a message saying why
+    /// it paused, if it did.
+    pub fn run_frame(&mut self, scheduler: &mut Scheduler, c8: &mut Chip8) -> Result<(StepOutcome, Option<String>), Chip8Error> {
+        if self.paused {
+            return Ok((StepOutcome::Continue, None));
+        }
+
+        let mut reason = None;
+        let outcome = {
+            let breakpoints = &self.breakpoints;
+            let watchpoints = &self.watchpoints;
+            let resume_at = &mut self.resume_at;
+            scheduler.run_frame_until(c8, |c8| {
+                if resume_at.take() == Some(c8.pc) {
+                    return false;
+                }
+                if let Some(range) = c8.last_write() {
+                    if let Some(&(start, _)) = watchpoints.iter().find(|&&(start, len)| range.start < start + len && start < range.end) {
+                        reason = Some(format!("Watchpoint 0x{:03X} written by 0x{:03X}", start, c8.pc.wrapping_sub(2)));
+                        return true;
+                    }
+                }
+                if breakpoints.contains(&c8.pc) {
+                    reason = Some(format!("Breakpoint at 0x{:03X}", c8.pc));
+                    return true;
+                }
+                false
+            })?
+        };
+
+        if reason.is_some() {
+            self.paused = true;
+        }
+        Ok((outcome.unwrap_or(StepOutcome::Continue), reason))
+    }
+
+    /// Carries out one command line, returning what to print.
+    pub fn command(&mut self, c8: &mut Chip8, line: &str) -> String {
+        match self.try_command(c8, line) {
+            Ok(out) => out,
+            Err(err) => err + "\n"
+        }
+    }
+
+    fn try_command(&mut self, c8: &mut Chip8, line: &str) -> Result<String, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let arg = |n: usize| -> Result<usize, String> {
+            parse_number(words.get(n).ok_or_else(|| format!("{} needs more arguments", words[0]))?)
+        };
+        let mut out = String::new();
+
+        match words.first().cloned() {
+            None => {},
+            Some("help") => out = HELP.to_string() + "\n",
+            Some("break") if words.len() == 1 => {
+                for addr in self.breakpoints.iter() {
+                    let _ = writeln!(out, "0x{:03X}", addr);
+                }
+            },
+            Some("break") => {
+                self.breakpoints.insert(arg(1)? as u16);
+            },
+            Some("delete") => {
+                self.breakpoints.take(&(arg(1)? as u16)).ok_or("No breakpoint there")?;
+            },
+            Some("watch") => {
+                let (start, len) = (arg(1)?, if words.len() > 2 { arg(2)? } else { 1 });
+                start.checked_add(len).ok_or("Watched range is too large")?;
+                self.watchpoints.push((start, len));
+            },
+            Some("unwatch") => {
+                let start = arg(1)?;
+                let before = self.watchpoints.len();
+                self.watchpoints.retain(|&(watched, _)| watched != start);
+                if self.watchpoints.len() == before {
+                    return Err("No watchpoint there".to_string());
+                }
+            },
+            Some("step") => {
+                let count = if words.len() > 1 { arg(1)? } else { 1 };
+                self.paused = true;
+                for _ in 0..count {
+                    match c8.step() {
+                        Ok(StepOutcome::Continue) => {},
+                        Ok(outcome) => {
+                            let _ = writeln!(out, "Stopped: {:?}", outcome);
+                            break;
+                        },
+                        Err(err) => return Err(err.to_string())
+                    }
+                }
+                out += &disassemble(c8, c8.pc as usize, 1);
+            },
+            Some("continue") => {
+                self.paused = false;
+                self.resume_at = Some(c8.pc);
+            },
+            Some("regs") => {
+                for (n, v) in c8.v.iter().enumerate() {
+                    let _ = write!(out, "V{:X}={:02X}{}", n, v, if n % 8 == 7 { "\n" } else { " " });
+                }
+                let _ = writeln!(out, "I={:03X} PC={:03X} SP={:X} DT={:02X} ST={:02X}",
+                                 c8.i, c8.pc, c8.sp, c8.delay_timer, c8.sound_timer);
+            },
+            Some("mem") => {
+                let (start, len) = (arg(1)?, arg(2)?);
+                if start.checked_add(len).is_none_or(|end| end > c8.memory.len()) {
+                    return Err("Out of memory bounds".to_string());
+                }
+                for (n, row) in c8.memory[start..start + len].chunks(16).enumerate() {
+                    let bytes: Vec<String> = row.iter().map(|byte| format!("{:02X}", byte)).collect();
+                    let _ = writeln!(out, "{:03X}: {}", start + n * 16, bytes.join(" "));
+                }
+            },
+            Some("stack") => {
+                if c8.sp == 0 {
+                    out += "Stack is empty\n";
+                }
+                for n in (0..c8.sp as usize).rev() {
+                    let _ = writeln!(out, "{:X}: {:03X}", n, c8.stack[n]);
+                }
+            },
+            Some("set") => {
+                let name = words.get(1).ok_or("set needs a register and a value")?.to_lowercase();
+                let value = arg(2)?;
+                match name.as_str() {
+                    "i"  => c8.i = value as u16,
+                    "pc" => c8.pc = value as u16,
+                    "dt" => c8.delay_timer = value as u8,
+                    "st" => c8.set_sound_timer(value as u8),
+                    _ if name.len() == 2 && name.starts_with('v') => {
+                        let n = usize::from_str_radix(&name[1..], 16).map_err(|_| format!("Unknown register: {}", name))?;
+                        c8.v[n] = value as u8;
+                    },
+                    _ => return Err(format!("Unknown register: {}", name))
+                }
+            },
+            Some("disasm") => {
+                let addr = if words.len() > 1 { arg(1)? } else { c8.pc as usize };
+                let count = if words.len() > 2 { arg(2)? } else { 8 };
+                out += &disassemble(c8, addr, count);
+            },
+            Some(command) => return Err(format!("Unknown command: {} (try help)", command))
+        }
+
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+fn test_machine() -> Chip8 {
+    use platform::Platform;
+
+    // 200: V0 += 1, 202: store BCD of V0 at 0x300, 204: jump to 200.
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.memory[0x200..0x206].copy_from_slice(&[0x70, 0x01, 0xF0, 0x33, 0x12, 0x00]);
+    c8.i = 0x300;
+    c8
+}
+
+#[test]
+fn test_debugger_breakpoint() {
+    let mut c8 = test_machine();
+    let mut scheduler = Scheduler::new(600);
+    let mut debugger = Debugger::new();
+
+    assert_eq!(debugger.run_frame(&mut scheduler, &mut c8), Ok((StepOutcome::Continue, None)));
+    assert_eq!(c8.v[0], 0, "Starts paused.");
+
+    debugger.command(&mut c8, "break 0x204");
+    debugger.command(&mut c8, "continue");
+    let (_, reason) = debugger.run_frame(&mut scheduler, &mut c8).unwrap();
+    assert_eq!(reason, Some("Breakpoint at 0x204".to_string()));
+    assert!(debugger.paused);
+    assert_eq!((c8.pc, c8.v[0]), (0x204, 1));
+
+    debugger.command(&mut c8, "continue");
+    debugger.run_frame(&mut scheduler, &mut c8).unwrap();
+    assert_eq!((c8.pc, c8.v[0]), (0x204, 2), "Ran past the breakpoint once.");
+}
+
+#[test]
+fn test_debugger_watchpoint() {
+    let mut c8 = test_machine();
+    let mut scheduler = Scheduler::new(600);
+    let mut debugger = Debugger::new();
+
+    debugger.command(&mut c8, "watch 0x302");
+    debugger.command(&mut c8, "continue");
+    let (_, reason) = debugger.run_frame(&mut scheduler, &mut c8).unwrap();
+    assert_eq!(reason, Some("Watchpoint 0x302 written by 0x202".to_string()));
+    assert_eq!(c8.memory[0x302], 1);
+
+    debugger.command(&mut c8, "continue");
+    debugger.run_frame(&mut scheduler, &mut c8).unwrap();
+    assert_eq!(c8.memory[0x302], 2, "Ran on to the next write.");
+}
+
+#[test]
+fn test_debugger_exit() {
+    use platform::Platform;
+
+    let mut c8 = test_machine();
+    c8.platform = Platform::SuperChip11;
+    c8.memory[0x200..0x202].copy_from_slice(&[0x00, 0xFD]);
+    let mut scheduler = Scheduler::new(600);
+    let mut debugger = Debugger::new();
+
+    debugger.command(&mut c8, "continue");
+    assert_eq!(debugger.run_frame(&mut scheduler, &mut c8), Ok((StepOutcome::Exit, None)));
+    assert_eq!(c8.pc, 0x200);
+}
+
+#[test]
+fn test_debugger_commands() {
+    use machine::SoundEvent;
+
+    let mut c8 = test_machine();
+    let mut debugger = Debugger::new();
+
+    assert_eq!(debugger.command(&mut c8, "step 2"), "=> 204: 1200  JP 0x200\n");
+    assert_eq!(debugger.command(&mut c8, "mem 0x300 3"), "300: 00 00 01\n");
+
+    debugger.command(&mut c8, "set v3 0x10");
+    debugger.command(&mut c8, "set pc 512");
+    assert_eq!((c8.v[3], c8.pc), (0x10, 0x200));
+    assert!(debugger.command(&mut c8, "regs").contains("V3=10"));
+
+    assert_eq!(debugger.command(&mut c8, "disasm 0x200 2"), "=> 200: 7001  ADD V0, 0x01\n   202: F033  LD B, V0\n");
+    assert_eq!(debugger.command(&mut c8, "set vg 1"), "Unknown register: vg\n");
+    assert_eq!(debugger.command(&mut c8, "mem 0xffff 0xffff"), "Out of memory bounds\n");
+    assert_eq!(debugger.command(&mut c8, &format!("watch 0x300 {}", usize::MAX)), "Watched range is too large\n");
+
+    debugger.command(&mut c8, "set st 10");
+    assert_eq!(c8.take_sound_events(), vec![SoundEvent::Start], "The buzzer hears about it.");
+    assert_eq!(debugger.command(&mut c8, "frobnicate"), "Unknown command: frobnicate (try help)\n");
+}
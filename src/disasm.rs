@@ -0,0 +1,193 @@
+//! Whole-ROM disassembly, separating code from data.
+//!
+//! Code is found by recursive descent from 0x200: following both ways
+//! out of skips, into jump and call targets, and on past calls. Bytes
+//! that cannot be reached that way are data and are listed as `db`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use instruction::{Instruction, InstructionSet};
+
+const START: usize = 0x200;
+
+/// How many data bytes are listed on each `db` line.
+const DB_PER_LINE: usize = 8;
+
+/// Disassembles `rom`, as loaded at 0x200, into a listing of addresses,
+/// opcodes and mnemonics. Only instructions in `set` are treated as code.
+pub fn disassemble_rom(rom: &[u8], set: InstructionSet) -> String {
+    let end = START + rom.len();
+    let word = |addr: usize| (rom[addr - START] as u16) << 8 | rom[addr - START + 1] as u16;
+    let decode = |addr: usize| {
+        if addr < START || addr + 1 >= end {
+            return None;
+        }
+        Instruction::decode(word(addr)).filter(|ins| ins.instruction_set() <= set)
+    };
+    // On XO-CHIP a skip steps over all four bytes of `F000 NNNN`.
+    let after = |addr: usize| {
+        if decode(addr) == Some(Instruction::LdILong) { addr + 4 } else { addr + 2 }
+    };
+
+    // Only targets inside the ROM get labels, as only they get lines.
+    let in_rom = |addr: u16| (START..end).contains(&(addr as usize));
+
+    // The length of the instruction at each address found to be code.
+    let mut code: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut labels: BTreeMap<usize, String> = BTreeMap::new();
+    let mut pending = vec![START];
+
+    while let Some(addr) = pending.pop() {
+        if code.contains_key(&addr) {
+            continue;
+        }
+        let ins = match decode(addr) {
+            Some(Instruction::LdILong) if addr + 3 >= end => continue,
+            Some(ins) => ins,
+            None => continue
+        };
+        code.insert(addr, if ins == Instruction::LdILong { 4 } else { 2 });
+
+        match ins {
+            Instruction::Jp(nnn) => {
+                if in_rom(nnn) {
+                    labels.entry(nnn as usize).or_insert_with(|| format!("L{:03X}", nnn));
+                }
+                pending.push(nnn as usize);
+            },
+            Instruction::JpV0(nnn) => {
+                // The target depends on V0; assume it is the start of a
+                // jump table.
+                if in_rom(nnn) {
+                    labels.entry(nnn as usize).or_insert_with(|| format!("L{:03X}", nnn));
+                }
+                pending.push(nnn as usize);
+            },
+            Instruction::Call(nnn) => {
+                if in_rom(nnn) {
+                    labels.insert(nnn as usize, format!("sub_{:03X}", nnn));
+                }
+                pending.push(nnn as usize);
+                pending.push(addr + 2);
+            },
+            Instruction::Ret | Instruction::Exit => {},
+            Instruction::SeImm { .. } | Instruction::SneImm { .. } | Instruction::SeReg { .. } |
+            Instruction::SneReg { .. } | Instruction::Skp { .. } | Instruction::Sknp { .. } => {
+                pending.push(addr + 2);
+                pending.push(after(addr + 2));
+            },
+            Instruction::LdILong => pending.push(addr + 4),
+            _ => pending.push(addr + 2)
+        }
+    }
+
+    let label = |nnn: u16| labels.get(&(nnn as usize)).cloned().unwrap_or_else(|| format!("0x{:03X}", nnn));
+    let text = |addr: usize| match Instruction::decode(word(addr)).unwrap() {
+        Instruction::Jp(nnn) => format!("JP {}", label(nnn)),
+        Instruction::JpV0(nnn) => format!("JP V0, {}", label(nnn)),
+        Instruction::Call(nnn) => format!("CALL {}", label(nnn)),
+        Instruction::LdILong => format!("LD I, 0x{:04X}", word(addr + 2)),
+        ins => ins.to_string()
+    };
+    let mut out = String::new();
+    let mut addr = START;
+    while addr < end {
+        if let Some(name) = labels.get(&addr) {
+            let _ = writeln!(out, "{}:", name);
+        }
+
+        if let Some(&len) = code.get(&addr) {
+            let raw = if len == 4 { format!("{:04X}{:04X}", word(addr), word(addr + 2)) } else { format!("{:04X}", word(addr)) };
+            let _ = writeln!(out, "{:03X}: {:<9} {}", addr, raw, text(addr));
+            // A jump into the middle of this instruction has no line of its
+            // own, so note where it lands and what runs from there.
+            for (&inside, name) in labels.range(addr + 1..addr + len) {
+                let _ = match code.get(&inside) {
+                    Some(_) => writeln!(out, "; {} at {:03X}, inside the instruction above: {:04X} {}", name, inside, word(inside), text(inside)),
+                    None => writeln!(out, "; {} at {:03X}, inside the instruction above", name, inside)
+                };
+            }
+            addr += len;
+        } else {
+            // Data runs until the next code or label, a line at a time.
+            let mut bytes = Vec::new();
+            while addr + bytes.len() < end && bytes.len() < DB_PER_LINE
+                && (bytes.is_empty() || !labels.contains_key(&(addr + bytes.len())))
+                && !code.contains_key(&(addr + bytes.len())) {
+                bytes.push(format!("0x{:02X}", rom[addr + bytes.len() - START]));
+            }
+            let _ = writeln!(out, "{:03X}: {:<9} db {}", addr, "", bytes.join(", "));
+            addr += bytes.len();
+        }
+    }
+    out
+}
+
+#[test]
+fn test_disassemble_rom() {
+    let rom = [
+        0x22, 0x08,             // 200: CALL sub_208
+        0x30, 0x01,             // 202: SE V0, 0x01
+        0x12, 0x02,             // 204: JP L202
+        0x00, 0xFD,             // 206: EXIT
+        0xA2, 0x0E,             // 208: LD I, 0x20E
+        0x00, 0xEE,             // 20A: RET
+        0x00, 0x00,             // 20C: unreachable
+        0xF0, 0x90, 0xF0        // 20E: sprite data
+    ];
+    let listing = disassemble_rom(&rom, InstructionSet::SuperChip11);
+    assert_eq!(listing, "\
+200: 2208      CALL sub_208
+L202:
+202: 3001      SE V0, 0x01
+204: 1202      JP L202
+206: 00FD      EXIT
+sub_208:
+208: A20E      LD I, 0x20E
+20A: 00EE      RET
+20C:           db 0x00, 0x00, 0xF0, 0x90, 0xF0
+");
+
+    let listing = disassemble_rom(&rom, InstructionSet::Chip8);
+    assert!(listing.contains("206:           db 0x00, 0xFD"), "EXIT is not CHIP-8:\n{}", listing);
+}
+
+#[test]
+fn test_disassemble_rom_long_load() {
+    let rom = [0x30, 0x00, 0xF0, 0x00, 0x12, 0x34, 0x12, 0x06];
+    let listing = disassemble_rom(&rom, InstructionSet::XoChip);
+    assert_eq!(listing, "\
+200: 3000      SE V0, 0x00
+202: F0001234  LD I, 0x1234
+L206:
+206: 1206      JP L206
+");
+}
+
+#[test]
+fn test_disassemble_rom_outside() {
+    // Targets outside the ROM, such as a routine in the interpreter, are
+    // left as addresses rather than labels with no line.
+    let rom = [0x21, 0x00, 0x22, 0x00, 0x1F, 0xFE];
+    let listing = disassemble_rom(&rom, InstructionSet::Chip8);
+    assert_eq!(listing, "\
+sub_200:
+200: 2100      CALL 0x100
+202: 2200      CALL sub_200
+204: 1FFE      JP 0xFFE
+");
+}
+
+#[test]
+fn test_disassemble_rom_label_inside() {
+    // The jump at 206 lands on the second half of the long load.
+    let rom = [0x30, 0x00, 0xF0, 0x00, 0x12, 0x04, 0x12, 0x04];
+    let listing = disassemble_rom(&rom, InstructionSet::XoChip);
+    assert_eq!(listing, "\
+200: 3000      SE V0, 0x00
+202: F0001204  LD I, 0x1204
+; L204 at 204, inside the instruction above: 1204 JP L204
+206: 1204      JP L204
+");
+}
@@ -0,0 +1,33 @@
+use std::error::Error;
+use std::fmt;
+
+/// A fault raised by the interpreter while executing an instruction.
+///
+/// When `execute` returns an error the program counter is left pointing
+/// at the faulting instruction, so the machine can be inspected as-is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Error {
+    /// The opcode at `pc` is not an instruction this interpreter knows.
+    UnknownOpcode { pc: u16, opcode: u16 },
+    /// A subroutine call was made with all 16 stack slots in use.
+    StackOverflow,
+    /// A return was made with an empty stack.
+    StackUnderflow,
+    /// An instruction tried to read or write memory past the end of RAM.
+    MemoryOutOfBounds { addr: usize },
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Chip8Error::UnknownOpcode { pc, opcode } =>
+                write!(f, "unknown opcode 0x{:04X} at 0x{:03X}", opcode, pc),
+            Chip8Error::StackOverflow => write!(f, "stack overflow"),
+            Chip8Error::StackUnderflow => write!(f, "return with an empty stack"),
+            Chip8Error::MemoryOutOfBounds { addr } =>
+                write!(f, "memory access out of bounds at 0x{:X}", addr),
+        }
+    }
+}
+
+impl Error for Chip8Error {}
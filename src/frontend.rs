@@ -0,0 +1,479 @@
+//! The SDL2 window frontend.
+
+mod audio;
+mod debug;
+mod gamepad;
+mod video;
+
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use chip8::{timestamped_path, write_png, Capture, Chip8, Keymap, Movie, Palette, Phosphor, Platform, Rewind, Scheduler, SoundEvent, StepOutcome, TIMER_HZ};
+use sdl2;
+use sdl2::event::WindowEvent;
+use sdl2::keyboard::Keycode;
+use sdl2::video::FullscreenType;
+
+use Options;
+use self::audio::Beeper;
+use self::debug::Console;
+use self::gamepad::Gamepads;
+use self::video::{display_area, Renderer};
+
+const TITLE:    &str =   "Chip8"; // Title to be displayed on the window.
+
+/// Keys for the quick save slots: press one to load its slot, with
+/// Shift to save to it.
+const SLOT_KEYS: [Keycode; 9] = [
+    Keycode::F1,
+    Keycode::F2,
+    Keycode::F3,
+    Keycode::F4,
+    Keycode::F5,
+    Keycode::F6,
+    Keycode::F7,
+    Keycode::F8,
+    Keycode::F9
+];
+
+/// How many times faster the program runs while fast-forwarding, and
+/// slower in slow motion.
+const FAST_FORWARD: u32 = 4;
+const SLOW_MOTION:  u32 = 4;
+
+/// Something the user asked the frontend, rather than the program, to do.
+enum Command {
+    Quit,
+    TogglePause,
+    Reset,              // Start the program again from scratch.
+    FrameAdvance,       // Run one frame while paused.
+    FastForward(bool),  // Start or stop fast-forwarding.
+    SlowMotion(bool),
+    ChangeSpeed(i32),   // Run this many more instructions each frame.
+    ToggleMute,
+    SaveState(usize),   // Quick save slot, from 1.
+    LoadState(usize),
+    Rewind(bool),       // Start or stop running backwards.
+    Screenshot,
+    ToggleCapture,      // Start or stop recording the screen.
+    ToggleFullscreen,
+    Redraw              // The window was resized or uncovered.
+}
+
+/// The length of one 60 Hz frame.
+fn frame_duration() -> Duration {
+    Duration::from_secs(1) / TIMER_HZ
+}
+
+/// Opens a window, loads the ROM named in `options` and runs it until
+/// the window is closed.
+///
+/// When recording, the movie is written once the window is closed. When
+/// replaying, the program stops after the movie's last frame and the
+/// screen is checked against the one recorded. When debugging, faults
+/// pause the program at the debug console instead of halting it.
+pub fn run(options: &Options) {
+    // Initialise Window and audio
+    let ctx = sdl2::init().unwrap();
+    let mut beeper = Beeper::new(&ctx, options.tone, options.muted);
+    let keymap = keycodes(&options.keymap).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+    let mut gamepads = Gamepads::new(&ctx, &options.keymap).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+
+    // Initialise chip8 and load game into memory
+    let seed = options.seed.unwrap_or_else(clock_seed);
+    let mut c8 = chip8_initialise(options, seed);
+    let mut scheduler = Scheduler::new(options.ips.unwrap_or_else(|| options.platform.default_ips()));
+    let palette = options.palette;
+    let mut phosphor = Phosphor::new(options.persistence);
+
+    let rom = fs::read(&options.rom).expect("Could not load file.");
+
+    let mut recording = options.record.as_ref().map(|_| Movie::record(&mut c8, &rom, scheduler.ips(), seed));
+    let replay = options.replay.as_ref().map(|path| {
+        let movie = File::open(path).and_then(|file| Movie::read(BufReader::new(file))).unwrap_or_else(|err| {
+            eprintln!("Could not read movie {}: {}", path, err);
+            std::process::exit(1);
+        });
+        if let Err(err) = movie.start_replay(&mut c8, &rom) {
+            eprintln!("Could not replay movie {}: {}", path, err);
+            std::process::exit(1);
+        }
+        scheduler.set_ips(movie.ips);
+        movie
+    });
+    let movie_active = recording.is_some() || replay.is_some();
+
+    // Open the window once the platform is settled, as a movie may change it.
+    let (mut canvas, mut events) = window_initialise(&ctx, c8.platform, options.scale);
+    let creator = canvas.texture_creator();
+    let mut renderer = Renderer::new(&creator);
+
+    // Rewinding or loading a state would make the movie's input meaningless.
+    let mut rewind = Rewind::new(if movie_active { 0 } else { options.rewind });
+    let mut frame = 0;
+
+    // Set once the program faults or exits. The window stays open,
+    // showing the last frame, until the user closes it.
+    let mut halted = false;
+    let mut rewinding = false;
+    let mut paused_by_user = false;
+    let mut advance = false;
+    let mut speed = frame_duration();
+    let mut console = if options.debug { Some(Console::new(&c8)) } else { None };
+    let mut capture = options.capture.as_ref().and_then(|path| start_capture(path, &palette, options.scale));
+    let mut redraw = false;
+    let mut next_frame = Instant::now();
+
+    'running: loop {
+        for command in chip8_handle_input(&mut c8, &keymap, &mut gamepads, &mut events) {
+            match command {
+                Command::Quit => break 'running,
+                Command::TogglePause => {
+                    paused_by_user = !paused_by_user;
+                    if !halted {
+                        set_status(&mut canvas, if paused_by_user { Some("paused") } else { None });
+                    }
+                },
+                Command::Reset if movie_active => {
+                    eprintln!("Cannot reset while recording or replaying a movie");
+                },
+                Command::Reset => {
+                    c8 = chip8_initialise(options, options.seed.unwrap_or_else(clock_seed));
+                    beeper.update(&[SoundEvent::Stop], true);
+                    // Rewinding must not reach back past the reset.
+                    rewind.clear();
+                    phosphor = Phosphor::new(options.persistence);
+                    frame = 0;
+                    set_status(&mut canvas, if paused_by_user { Some("paused") } else { None });
+                    halted = false;
+                },
+                Command::FrameAdvance => advance = paused_by_user,
+                Command::FastForward(on) => speed = if on { frame_duration() / FAST_FORWARD } else { frame_duration() },
+                Command::SlowMotion(on) => speed = if on { frame_duration() * SLOW_MOTION } else { frame_duration() },
+                Command::ChangeSpeed(_) if movie_active => {
+                    eprintln!("Cannot change speed while recording or replaying a movie");
+                },
+                Command::ChangeSpeed(change) => {
+                    let per_frame = (scheduler.ips() / TIMER_HZ) as i32 + change;
+                    scheduler.set_ips(per_frame.max(1) as u32 * TIMER_HZ);
+                    eprintln!("Running {} instructions per frame", scheduler.ips() / TIMER_HZ);
+                },
+                Command::ToggleMute => beeper.toggle_mute(),
+                Command::SaveState(slot) => {
+                    let path = state_path(&options.rom, slot);
+                    match File::create(&path).and_then(|mut file| c8.save_state(&mut file)) {
+                        Ok(()) => eprintln!("Saved state to {}", path),
+                        Err(err) => eprintln!("Could not save state to {}: {}", path, err)
+                    }
+                },
+                Command::LoadState(_) if movie_active => {
+                    eprintln!("Cannot load a state while recording or replaying a movie");
+                },
+                Command::LoadState(slot) => {
+                    let path = state_path(&options.rom, slot);
+                    match File::open(&path).and_then(|mut file| c8.load_state(&mut file)) {
+                        Ok(()) => {
+                            eprintln!("Loaded state from {}", path);
+                            set_status(&mut canvas, None);
+                            halted = false;
+                        },
+                        Err(err) => eprintln!("Could not load state from {}: {}", path, err)
+                    }
+                },
+                Command::Rewind(on) => rewinding = on,
+                Command::Screenshot => {
+                    // Save the picture as it appears in the window.
+                    let path = timestamped_path(&options.rom, "png", SystemTime::now());
+                    let scale = display_area(&c8, &canvas).2;
+                    match File::create(&path).and_then(|file| write_png(&c8, &palette, scale, BufWriter::new(file))) {
+                        Ok(()) => eprintln!("Saved screenshot to {}", path),
+                        Err(err) => eprintln!("Could not save screenshot to {}: {}", path, err)
+                    }
+                },
+                Command::ToggleCapture => match capture.take() {
+                    Some((recording, path)) => finish_capture(recording, &path),
+                    None => capture = start_capture(&timestamped_path(&options.rom, "gif", SystemTime::now()), &palette, options.scale)
+                },
+                Command::ToggleFullscreen => {
+                    let window = canvas.window_mut();
+                    let fullscreen = match window.fullscreen_state() {
+                        FullscreenType::Off => FullscreenType::Desktop,
+                        _ => FullscreenType::Off
+                    };
+                    if let Err(err) = window.set_fullscreen(fullscreen) {
+                        eprintln!("Could not change to or from fullscreen: {}", err);
+                    }
+                },
+                Command::Redraw => redraw = true
+            }
+        }
+        if let Some(ref mut console) = console {
+            console.poll(&mut c8);
+        }
+        let paused = console.as_ref().is_some_and(Console::paused) || (paused_by_user && !advance);
+        advance = false;
+
+        let running = !rewinding && !halted && !paused;
+        if rewinding {
+            // Step back a frame instead of running one. Rewinding past
+            // a fault or exit lets the program run again.
+            if rewind.rewind(&mut c8) && halted {
+                set_status(&mut canvas, None);
+                halted = false;
+            }
+        } else if running {
+            if let Some(ref movie) = replay {
+                c8.key = movie.keys_at(frame);
+            }
+            if let Some(ref mut movie) = recording {
+                movie.record_frame(frame, &c8.key);
+            }
+            let result = match console {
+                Some(ref mut console) => console.run_frame(&mut scheduler, &mut c8),
+                None => scheduler.run_frame(&mut c8)
+            };
+            let status = match result {
+                Ok(StepOutcome::Exit) => Some("exited".to_string()),
+                Ok(_) => None,
+                Err(err) => Some(format!("halted: {}", err))
+            };
+            match (status, console.as_mut()) {
+                (Some(status), Some(console)) => console.stop(&c8, &format!("Program {}", status)),
+                (Some(status), None) => {
+                    eprintln!("Program {}", status);
+                    set_status(&mut canvas, Some(&status));
+                    halted = true;
+                },
+                (None, _) => {}
+            }
+            rewind.push(&c8);
+            frame += 1;
+
+            if let Some(ref movie) = replay {
+                if halted || frame == movie.frames {
+                    let status = if movie.verify(&c8) { "replay verified" } else { "replay desynced" };
+                    eprintln!("Movie {} after {} frames", status, frame);
+                    set_status(&mut canvas, Some(status));
+                    halted = true;
+                }
+            }
+        }
+        beeper.set_pattern(c8.audio_pattern());
+        beeper.update(&c8.take_sound_events(), running);
+        // Draw at most once a frame, however many sprites were drawn.
+        if phosphor.update(&c8) || c8.draw_flag || redraw {
+            renderer.draw(&c8, &phosphor.colours(&palette), &mut canvas);
+            c8.draw_flag = false;
+            redraw = false;
+        }
+        let failed = match capture {
+            Some((ref mut recording, _)) => recording.capture(&c8).err(),
+            None => None
+        };
+        if let Some(err) = failed {
+            eprintln!("Stopped recording the screen: {}", err);
+            capture = None;
+        }
+
+        // Sleep until the next frame is due. If we have fallen behind,
+        // start counting again from now rather than racing to catch up.
+        next_frame += speed;
+        let now = Instant::now();
+        if next_frame > now {
+            thread::sleep(next_frame - now);
+        } else {
+            next_frame = now;
+        }
+    }
+
+    if let (Some(mut movie), Some(path)) = (recording, options.record.as_ref()) {
+        movie.finish(&c8);
+        match File::create(path).and_then(|mut file| movie.write(&mut file)) {
+            Ok(()) => eprintln!("Recorded {} frames to {}", movie.frames, path),
+            Err(err) => eprintln!("Could not write movie {}: {}", path, err)
+        }
+    }
+    if let Some((recording, path)) = capture {
+        finish_capture(recording, &path);
+    }
+}
+
+/// Starts recording the screen to `path`, at the size `--scale` gives
+/// the window.
+fn start_capture(path: &str, palette: &Palette, scale: u32) -> Option<(Capture, String)> {
+    match Capture::create(path, palette, scale / 2) {
+        Ok(recording) => {
+            eprintln!("Recording the screen to {}", path);
+            Some((recording, path.to_string()))
+        },
+        Err(err) => {
+            eprintln!("Could not record the screen to {}: {}", path, err);
+            None
+        }
+    }
+}
+
+fn finish_capture(recording: Capture, path: &str) {
+    match recording.finish() {
+        Ok(frames) => eprintln!("Recorded {} frames of the screen to {}", frames, path),
+        Err(err) => eprintln!("Could not finish recording the screen to {}: {}", path, err)
+    }
+}
+
+/// A seed for the random number generator, taken from the clock.
+fn clock_seed() -> u64 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    now.as_secs() << 32 ^ now.subsec_nanos() as u64
+}
+
+/// Shows `status`, if any, after the window title.
+fn set_status(canvas: &mut sdl2::render::Canvas<sdl2::video::Window>, status: Option<&str>) {
+    let title = match status {
+        Some(status) => format!("{} - {}", TITLE, status),
+        None => TITLE.to_string()
+    };
+    canvas.window_mut().set_title(&title).expect("Could not set window title.");
+}
+
+/// Looks up the keys `keymap` names.
+fn keycodes(keymap: &Keymap) -> Result<[Keycode; 16], String> {
+    let mut keycodes = [Keycode::Escape; 16];
+    for (keycode, name) in keycodes.iter_mut().zip(keymap.keys.iter()) {
+        *keycode = Keycode::from_name(name).ok_or_else(|| format!("Unknown key: {}", name))?;
+    }
+    Ok(keycodes)
+}
+
+/// A new machine for the platform and quirks in `options`, with its
+/// random numbers seeded by `seed` and the ROM loaded.
+fn chip8_initialise(options: &Options, seed: u64) -> Chip8 {
+    let mut c8 = Chip8::new(options.platform);
+    if let Some(quirks) = options.quirks {
+        c8.quirks = quirks;
+    }
+    c8.rng = options.create_rng(seed);
+    c8.load_game(&options.rom).expect("Could not load file.");
+    c8
+}
+
+/// The file quick save `slot` is kept in, next to the ROM.
+fn state_path(rom: &str, slot: usize) -> String {
+    format!("{}.{}.state", rom, slot)
+}
+
+/// Initialise a new SDL2 window.
+///
+/// Creates a video context and event pump from the sdl2 context, and
+/// from the video context a resizable window with `scale` window pixels
+/// to each lo-res pixel. The window is never smaller than
+/// `platform.resolution()`, so the largest display always fits. Returns
+/// the window's canvas (for later rendering to) and the event pump (to
+/// detect key presses).
+///
+/// # Panics
+/// If the window cannot be created from the video context the
+/// program will panic.
+fn window_initialise(ctx: &sdl2::Sdl, platform: Platform, scale: u32) -> (sdl2::render::Canvas<sdl2::video::Window>, sdl2::EventPump) {
+    let video_ctx = ctx.video().unwrap();
+    let events = ctx.event_pump().unwrap();
+
+    let (min_width, min_height) = platform.resolution();
+    let (min_width, min_height) = (min_width as u32, min_height as u32);
+    let (width, height) = ((64 * scale).max(min_width), (32 * scale).max(min_height));
+    let mut window = match video_ctx.window(TITLE, width, height).position_centered().resizable().opengl().build() {
+        Ok(window) => window,
+        Err(err) => panic!("Failed to create window: {}", err)
+    };
+    window.set_minimum_size(min_width, min_height).expect("Could not set window size.");
+
+    window.show();
+    let canvas = window.into_canvas().build().unwrap();
+
+    (canvas, events)
+}
+
+/// Updates c8.key from the keyboard and controllers and returns any
+/// commands for the frontend itself.
+fn chip8_handle_input(c8: &mut Chip8, keymap: &[Keycode; 16], gamepads: &mut Gamepads, events: &mut sdl2::EventPump) -> Vec<Command> {
+    let mut commands = Vec::new();
+
+    for event in events.poll_iter() {
+        match event {
+            sdl2::event::Event::Quit{..} => commands.push(Command::Quit),
+            sdl2::event::Event::Window {win_event: WindowEvent::SizeChanged(..), ..} |
+            sdl2::event::Event::Window {win_event: WindowEvent::Exposed, ..} => commands.push(Command::Redraw),
+            sdl2::event::Event::KeyDown {keycode: Some(keycode), keymod, repeat, ..} => {
+                if keycode == sdl2::keyboard::Keycode::Escape {
+                    commands.push(Command::Quit);
+                }
+                if keycode == Keycode::Return && keymod.intersects(sdl2::keyboard::LALTMOD | sdl2::keyboard::RALTMOD) {
+                    commands.push(Command::ToggleFullscreen);
+                    continue;
+                }
+                if keycode == Keycode::R && keymod.intersects(sdl2::keyboard::LCTRLMOD | sdl2::keyboard::RCTRLMOD) {
+                    commands.push(Command::Reset);
+                    continue;
+                }
+                match keycode {
+                    Keycode::P => commands.push(Command::TogglePause),
+                    Keycode::N => commands.push(Command::FrameAdvance),
+                    Keycode::Tab if !repeat => commands.push(Command::FastForward(true)),
+                    Keycode::Backquote if !repeat => commands.push(Command::SlowMotion(true)),
+                    Keycode::Equals | Keycode::Plus => commands.push(Command::ChangeSpeed(1)),
+                    Keycode::Minus => commands.push(Command::ChangeSpeed(-1)),
+                    _ => {}
+                }
+                if keycode == Keycode::Backspace && !repeat {
+                    commands.push(Command::Rewind(true));
+                }
+                if keycode == Keycode::M {
+                    commands.push(Command::ToggleMute);
+                }
+                if keycode == Keycode::F10 && !repeat {
+                    commands.push(Command::ToggleCapture);
+                }
+                if keycode == Keycode::F12 && !repeat {
+                    commands.push(Command::Screenshot);
+                }
+                if let Some(i) = SLOT_KEYS.iter().position(|&key| key == keycode) {
+                    if keymod.intersects(sdl2::keyboard::LSHIFTMOD | sdl2::keyboard::RSHIFTMOD) {
+                        commands.push(Command::SaveState(i + 1));
+                    } else {
+                        commands.push(Command::LoadState(i + 1));
+                    }
+                }
+
+                if let Some(i) = keymap.iter().position(|&key| key == keycode) {
+                    c8.key[i] = 1;
+                }
+            },
+            sdl2::event::Event::KeyUp {keycode: Some(keycode), ..} => {
+                if keycode == Keycode::Backspace {
+                    commands.push(Command::Rewind(false));
+                }
+                if keycode == Keycode::Tab {
+                    commands.push(Command::FastForward(false));
+                }
+                if keycode == Keycode::Backquote {
+                    commands.push(Command::SlowMotion(false));
+                }
+                if let Some(i) = keymap.iter().position(|&key| key == keycode) {
+                    c8.key[i] = 0;
+                }
+            },
+            event                        => gamepads.handle(c8, &event)
+        }
+    }
+
+    commands
+}
+
@@ -0,0 +1,115 @@
+//! The buzzer, played through an SDL2 audio device.
+
+use chip8::{Pattern, SoundEvent, Tone};
+use sdl2;
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+
+const SAMPLE_RATE: i32 = 44100;
+
+/// Feeds the audio device samples of a tone, or of an XO-CHIP pattern.
+struct Oscillator {
+    tone:    Tone,
+    phase:   f32,   // Position through the current cycle, from 0.0 up to 1.0.
+    step:    f32,   // How far the phase moves each sample.
+    pattern: Option<(Pattern, f32)>, // The pattern playing instead of the tone, and its samples per device sample.
+    freq:    f32    // The device's sample rate.
+}
+
+impl AudioCallback for Oscillator {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            match self.pattern {
+                Some((ref pattern, step)) => {
+                    *sample = pattern.sample(self.phase, self.tone.volume);
+                    self.phase = (self.phase + step) % 128.0;
+                },
+                None => {
+                    *sample = self.tone.sample(self.phase);
+                    self.phase = (self.phase + self.step).fract();
+                }
+            }
+        }
+    }
+}
+
+/// Plays the tone while the sound timer is sounding.
+pub struct Beeper {
+    device:   Option<AudioDevice<Oscillator>>,  // None if no audio device could be opened.
+    pattern:  Option<Pattern>,
+    sounding: bool,
+    running:  bool,     // Whether the machine ran the last frame.
+    muted:    bool
+}
+
+impl Beeper {
+    /// Opens the default audio device. If there is none the program runs
+    /// on in silence.
+    pub fn new(ctx: &sdl2::Sdl, tone: Tone, muted: bool) -> Beeper {
+        let desired = AudioSpecDesired { freq: Some(SAMPLE_RATE), channels: Some(1), samples: None };
+        let device = ctx.audio().and_then(|audio| audio.open_playback(None, &desired, |spec| {
+            Oscillator { tone, phase: 0.0, step: tone.frequency / spec.freq as f32, pattern: None, freq: spec.freq as f32 }
+        }));
+
+        let device = match device {
+            Ok(device) => Some(device),
+            Err(err) => {
+                eprintln!("Could not open audio device, sound is disabled: {}", err);
+                None
+            }
+        };
+
+        Beeper { device, pattern: None, sounding: false, running: true, muted }
+    }
+
+    /// Starts or stops the tone for the sound events of the last frame.
+    /// The tone is silent while the machine is not `running`, so a beep
+    /// does not drone on while paused, and picks up again on resuming.
+    ///
+    /// A beep that starts and stops within one frame still sounds until
+    /// the next call, so that short beeps are heard.
+    pub fn update(&mut self, events: &[SoundEvent], running: bool) {
+        let mut started = false;
+        for event in events {
+            match *event {
+                SoundEvent::Start => {
+                    self.sounding = true;
+                    started = true;
+                },
+                SoundEvent::Stop => self.sounding = false
+            }
+        }
+        self.running = running;
+        self.play(running && (self.sounding || started));
+    }
+
+    /// Plays `pattern` in place of the tone, or the tone again if None.
+    pub fn set_pattern(&mut self, pattern: Option<Pattern>) {
+        if pattern == self.pattern {
+            return;
+        }
+        self.pattern = pattern;
+        if let Some(ref mut device) = self.device {
+            let mut oscillator = device.lock();
+            let freq = oscillator.freq;
+            oscillator.pattern = pattern.map(|pattern| (pattern, pattern.rate() / freq));
+            oscillator.phase = 0.0;
+        }
+    }
+
+    pub fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+        self.play(self.running && self.sounding);
+    }
+
+    fn play(&self, on: bool) {
+        if let Some(ref device) = self.device {
+            if on && !self.muted {
+                device.resume();
+            } else {
+                device.pause();
+            }
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! The `--debug` console: debugger commands read from stdin while the
+//! window carries on rendering.
+
+use std::io;
+use std::io::prelude::*;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use chip8::{disassemble, Chip8, Chip8Error, Debugger, Scheduler, StepOutcome};
+
+const PROMPT: &str = "(chip8) ";
+
+pub struct Console {
+    debugger: Debugger,
+    lines:    Receiver<String>  // Lines typed at stdin, read on another thread.
+}
+
+impl Console {
+    /// Starts reading commands from stdin. The program starts paused.
+    pub fn new(c8: &Chip8) -> Console {
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            let stdin = io::stdin();
+            for line in stdin.lock().lines() {
+                match line {
+                    Ok(line) => if sender.send(line).is_err() { break },
+                    Err(_) => break
+                }
+            }
+        });
+
+        println!("Paused at 0x{:03X}. Type help for a list of commands.", c8.pc);
+        prompt();
+        Console { debugger: Debugger::new(), lines }
+    }
+
+    pub fn paused(&self) -> bool {
+        self.debugger.paused
+    }
+
+    /// Carries out any commands typed since the last call.
+    pub fn poll(&mut self, c8: &mut Chip8) {
+        for line in self.lines.try_iter() {
+            print!("{}", self.debugger.command(c8, &line));
+            prompt();
+        }
+    }
+
+    /// Runs a frame unless paused, pausing at breakpoints and watchpoints.
+    /// Returns how the frame ended, so the caller can stop on exit.
+    pub fn run_frame(&mut self, scheduler: &mut Scheduler, c8: &mut Chip8) -> Result<StepOutcome, Chip8Error> {
+        let (outcome, reason) = self.debugger.run_frame(scheduler, c8)?;
+        if let Some(reason) = reason {
+            self.stop(c8, &reason);
+        }
+        Ok(outcome)
+    }
+
+    /// Pauses after the program faulted or exited, so it can be inspected.
+    pub fn stop(&mut self, c8: &Chip8, reason: &str) {
+        self.debugger.paused = true;
+        println!();
+        println!("{}", reason);
+        print!("{}", disassemble(c8, c8.pc as usize, 1));
+        prompt();
+    }
+}
+
+fn prompt() {
+    print!("{}", PROMPT);
+    let _ = io::stdout().flush();
+}
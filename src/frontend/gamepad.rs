@@ -0,0 +1,120 @@
+//! Game controllers, through SDL's GameController API.
+
+use std::collections::BTreeMap;
+
+use chip8::{Chip8, Keymap};
+use sdl2;
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event;
+use sdl2::GameControllerSubsystem;
+
+/// How far a stick must be pushed, out of 32767, to press a key.
+const STICK_THRESHOLD: i16 = 16384;
+
+/// A control on a controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Control {
+    Button(Button),
+    Stick(Axis, bool)   // An axis, pushed in the positive or negative direction.
+}
+
+/// The connected controllers and the hex keys their controls press.
+pub struct Gamepads {
+    subsystem: Option<GameControllerSubsystem>,
+    open: Vec<GameController>,
+    bindings: Vec<(Control, usize)>,
+    pushed: Vec<(i32, Control)>     // Stick directions held past the threshold, by controller.
+}
+
+impl Gamepads {
+    /// Resolves the buttons in `keymap`. Controllers are opened as SDL
+    /// reports them connected, which it does at startup for any already
+    /// plugged in. Without the controller subsystem, there are none.
+    pub fn new(ctx: &sdl2::Sdl, keymap: &Keymap) -> Result<Gamepads, String> {
+        let subsystem = match ctx.game_controller() {
+            Ok(subsystem) => Some(subsystem),
+            Err(err) => {
+                eprintln!("No game controller support: {}", err);
+                None
+            }
+        };
+        Ok(Gamepads {
+            subsystem,
+            open: Vec::new(),
+            bindings: bindings(&keymap.buttons)?,
+            pushed: Vec::new()
+        })
+    }
+
+    /// Opens and closes controllers as they are plugged in and out, and
+    /// presses the hex keys bound to their controls.
+    pub fn handle(&mut self, c8: &mut Chip8, event: &Event) {
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => {
+                let subsystem = match self.subsystem {
+                    Some(ref subsystem) => subsystem,
+                    None => return
+                };
+                match subsystem.open(which) {
+                    Ok(controller) => {
+                        eprintln!("Connected {}", controller.name());
+                        self.open.push(controller);
+                    },
+                    Err(err) => eprintln!("Could not open controller {}: {}", which, err)
+                }
+            },
+            Event::ControllerDeviceRemoved { which, .. } => {
+                self.open.retain(|controller| {
+                    let removed = controller.instance_id() == which;
+                    if removed {
+                        eprintln!("Disconnected {}", controller.name());
+                    }
+                    !removed
+                });
+                // Let go of whatever its sticks were holding.
+                let (gone, kept) = self.pushed.drain(..).partition(|&(id, _)| id == which);
+                self.pushed = kept;
+                for (_, control) in gone {
+                    self.press(c8, control, false);
+                }
+            },
+            Event::ControllerButtonDown { button, .. } => self.press(c8, Control::Button(button), true),
+            Event::ControllerButtonUp { button, .. } => self.press(c8, Control::Button(button), false),
+            Event::ControllerAxisMotion { which, axis, value, .. } => {
+                // Sticks report every small movement; only press or release
+                // as one crosses the threshold, so that a stick at rest does
+                // not release keys held by other controls.
+                for &(positive, past) in [(true, value > STICK_THRESHOLD), (false, value < -STICK_THRESHOLD)].iter() {
+                    let control = Control::Stick(axis, positive);
+                    let held = self.pushed.contains(&(which, control));
+                    if past && !held {
+                        self.pushed.push((which, control));
+                        self.press(c8, control, true);
+                    } else if !past && held {
+                        self.pushed.retain(|&pushed| pushed != (which, control));
+                        self.press(c8, control, false);
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn press(&self, c8: &mut Chip8, control: Control, down: bool) {
+        for &(_, key) in self.bindings.iter().filter(|&&(bound, _)| bound == control) {
+            c8.key[key] = down as u8;
+        }
+    }
+}
+
+/// Looks up the controls `buttons` names.
+fn bindings(buttons: &BTreeMap<String, usize>) -> Result<Vec<(Control, usize)>, String> {
+    buttons.iter().map(|(name, &key)| {
+        let control = match (name.strip_suffix('+'), name.strip_suffix('-')) {
+            (Some(axis), _) => Axis::from_string(axis).map(|axis| Control::Stick(axis, true)),
+            (_, Some(axis)) => Axis::from_string(axis).map(|axis| Control::Stick(axis, false)),
+            _ => Button::from_string(name).map(Control::Button)
+        };
+        control.map(|control| (control, key)).ok_or_else(|| format!("Unknown controller button: {}", name))
+    }).collect()
+}
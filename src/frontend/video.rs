@@ -0,0 +1,68 @@
+//! Drawing the display into the window.
+
+use chip8::{Chip8, Colour};
+use sdl2;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, Texture, TextureCreator};
+use sdl2::video::{Window, WindowContext};
+
+const LETTERBOX: Colour = (0, 0, 0); // Colour around the display when the window's shape differs.
+
+/// Draws frames by uploading them to a streaming texture the size of the
+/// display, which SDL then stretches over the window.
+pub struct Renderer<'a> {
+    creator: &'a TextureCreator<WindowContext>,
+    texture: Option<(Texture<'a>, (usize, usize))> // The texture and the resolution it was made for.
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(creator: &'a TextureCreator<WindowContext>) -> Renderer<'a> {
+        // Keep pixels square-edged when stretching them.
+        sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "nearest");
+        Renderer {
+            creator,
+            texture: None
+        }
+    }
+
+    /// Clears the window and draws `colours`, one for each pixel of
+    /// c8.gfx at whichever resolution is active, in the middle of it.
+    pub fn draw(&mut self, c8: &Chip8, colours: &[Colour], canvas: &mut Canvas<Window>) {
+        let (width, height) = c8.display_size();
+        if self.texture.as_ref().is_none_or(|&(_, size)| size != (width, height)) {
+            let texture = self.creator.create_texture_streaming(PixelFormatEnum::RGB24, width as u32, height as u32)
+                .expect("Could not create texture.");
+            self.texture = Some((texture, (width, height)));
+        }
+        let texture = &mut self.texture.as_mut().unwrap().0;
+
+        texture.with_lock(None, |buffer, pitch| {
+            for (row, line) in colours.chunks(width).zip(buffer.chunks_mut(pitch)) {
+                for (&(r, g, b), pixel) in row.iter().zip(line.chunks_mut(3)) {
+                    pixel.copy_from_slice(&[r, g, b]);
+                }
+            }
+        }).expect("Could not draw to screen.");
+
+        let (left, top, pixel) = display_area(c8, canvas);
+        let (r, g, b) = LETTERBOX;
+        canvas.set_draw_color(sdl2::pixels::Color::RGB(r, g, b));
+        canvas.clear();
+        canvas.copy(texture, None, Rect::new(left, top, width as u32 * pixel, height as u32 * pixel))
+            .expect("Could not draw to screen.");
+        canvas.present();
+    }
+}
+
+/// Where the display goes in the window, as the left and top of its
+/// rectangle and the size of one pixel: the most whole window pixels per
+/// display pixel that fit, centred, with letterbox bars around the rest.
+pub fn display_area(c8: &Chip8, canvas: &Canvas<Window>) -> (i32, i32, u32) {
+    let (width, height) = c8.display_size();
+    let (window_width, window_height) = canvas.output_size().expect("Could not get window size.");
+    let pixel = (window_width / width as u32).min(window_height / height as u32).max(1);
+    let left = (window_width as i32 - (width as u32 * pixel) as i32) / 2;
+    let top = (window_height as i32 - (height as u32 * pixel) as i32) / 2;
+    (left, top, pixel)
+}
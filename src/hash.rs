@@ -0,0 +1,18 @@
+/// Hashes `data` with 64 bit FNV-1a.
+///
+/// Used to checksum save states and to identify ROMs and screens; it is
+/// fast and stable across builds, not cryptographic.
+pub fn fnv1a(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xCBF2_9CE4_8422_2325;
+    for &byte in data {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+    }
+    hash
+}
+
+#[test]
+fn test_fnv1a() {
+    assert_eq!(fnv1a(b""), 0xCBF2_9CE4_8422_2325);
+    assert_eq!(fnv1a(b"a"), 0xAF63_DC4C_8601_EC8C);
+}
@@ -0,0 +1,99 @@
+//! `chip8 run --headless`: runs a ROM without a window, for tests and CI.
+
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+
+use chip8::{fnv1a, screen_text, write_png, Capture, Chip8, InputScript, Movie, Scheduler, StepOutcome};
+
+use Options;
+
+/// Runs the ROM for `options.frames` frames, or until it exits, and
+/// prints a hash of the final screen. A movie given with `--replay`
+/// supplies the input and the number of frames instead. Returns the
+/// process's exit code: non-zero if the program faulted, a replay
+/// desynced or anything could not be read or written.
+pub fn run(options: &Options) -> i32 {
+    match run_headless(options) {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
+    }
+}
+
+fn run_headless(options: &Options) -> Result<(), String> {
+    let mut c8 = Chip8::new(options.platform);
+    if let Some(quirks) = options.quirks {
+        c8.quirks = quirks;
+    }
+    // Runs are reproducible unless a seed says otherwise.
+    c8.rng = options.create_rng(options.seed.unwrap_or(0));
+    let mut scheduler = Scheduler::new(options.ips.unwrap_or_else(|| options.platform.default_ips()));
+    c8.load_game(&options.rom).map_err(|err| format!("Could not load {}: {}", options.rom, err))?;
+
+    let script = match options.input {
+        Some(ref path) => {
+            let text = fs::read_to_string(path).map_err(|err| format!("Could not read {}: {}", path, err))?;
+            InputScript::parse(&text).map_err(|err| format!("{}: {}", path, err))?
+        },
+        None => InputScript::default()
+    };
+    let replay = match options.replay {
+        Some(ref path) => {
+            let rom = fs::read(&options.rom).map_err(|err| format!("Could not load {}: {}", options.rom, err))?;
+            let movie = File::open(path).and_then(|file| Movie::read(BufReader::new(file)))
+                .map_err(|err| format!("Could not read movie {}: {}", path, err))?;
+            movie.start_replay(&mut c8, &rom).map_err(|err| format!("Could not replay movie {}: {}", path, err))?;
+            scheduler.set_ips(movie.ips);
+            Some(movie)
+        },
+        None => None
+    };
+    let frames = replay.as_ref().map_or(options.frames, |movie| movie.frames);
+    let mut capture = match options.capture {
+        Some(ref path) => Some(Capture::create(path, &options.palette, options.scale / 2)
+            .map_err(|err| format!("Could not record the screen to {}: {}", path, err))?),
+        None => None
+    };
+
+    let mut fault = None;
+    for frame in 0..frames {
+        match replay {
+            Some(ref movie) => c8.key = movie.keys_at(frame),
+            None => script.apply(frame, &mut c8)
+        }
+        let result = scheduler.run_frame(&mut c8);
+        if let Some(ref mut capture) = capture {
+            capture.capture(&c8).map_err(|err| format!("Could not record the screen: {}", err))?;
+        }
+        match result {
+            Ok(StepOutcome::Exit) => break,
+            Ok(_) => {},
+            Err(err) => {
+                fault = Some(format!("Frame {}: {}", frame, err));
+                break;
+            }
+        }
+    }
+
+    if let Some(capture) = capture {
+        capture.finish().map_err(|err| format!("Could not record the screen: {}", err))?;
+    }
+    if let Some(ref path) = options.dump_screen {
+        let written = if path.ends_with(".png") {
+            File::create(path).and_then(|file| write_png(&c8, &options.palette, 1, BufWriter::new(file)))
+        } else {
+            fs::write(path, screen_text(&c8))
+        };
+        written.map_err(|err| format!("Could not write {}: {}", path, err))?;
+    }
+    println!("{:016x}", fnv1a(&c8.gfx));
+
+    if let Some(ref movie) = replay {
+        if fault.is_none() && !movie.verify(&c8) {
+            fault = Some("Movie replay desynced".to_string());
+        }
+    }
+    fault.map_or(Ok(()), Err)
+}
@@ -0,0 +1,387 @@
+use std::fmt;
+
+/// A single decoded CHIP-8 instruction.
+///
+/// `x` and `y` are register indices (0-F), `nn` an 8 bit immediate,
+/// `n` a 4 bit immediate and the `u16` payloads 12 bit addresses.
+/// Variant names follow the mnemonics in Cowgod's technical reference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// 00E0 - Clear the screen.
+    Cls,
+    /// 00EE - Return from subroutine.
+    Ret,
+    /// 00CN - Scroll the display down N pixels. SUPER-CHIP 1.1.
+    Scd(u8),
+    /// 00DN - Scroll the display up N pixels. XO-CHIP.
+    Scu(u8),
+    /// 00FB - Scroll the display right 4 pixels. SUPER-CHIP 1.1.
+    Scr,
+    /// 00FC - Scroll the display left 4 pixels. SUPER-CHIP 1.1.
+    Scl,
+    /// 00FD - Exit the interpreter. SUPER-CHIP.
+    Exit,
+    /// 00FE - Switch to the 64x32 low resolution display. SUPER-CHIP.
+    Low,
+    /// 00FF - Switch to the 128x64 high resolution display. SUPER-CHIP.
+    High,
+    /// 1NNN - Jump to address NNN.
+    Jp(u16),
+    /// 2NNN - Call subroutine at NNN.
+    Call(u16),
+    /// 3XNN - Skip next instruction if Vx == NN.
+    SeImm { x: u8, nn: u8 },
+    /// 4XNN - Skip next instruction if Vx != NN.
+    SneImm { x: u8, nn: u8 },
+    /// 5XY0 - Skip next instruction if Vx == Vy.
+    SeReg { x: u8, y: u8 },
+    /// 5XY2 - Store Vx to Vy, in either order, in memory starting at I. XO-CHIP.
+    SaveRange { x: u8, y: u8 },
+    /// 5XY3 - Fill Vx to Vy, in either order, from memory starting at I. XO-CHIP.
+    LoadRange { x: u8, y: u8 },
+    /// 6XNN - Set Vx to NN.
+    LdImm { x: u8, nn: u8 },
+    /// 7XNN - Add NN to Vx, carry flag unchanged.
+    AddImm { x: u8, nn: u8 },
+    /// 8XY0 - Set Vx to Vy.
+    LdReg { x: u8, y: u8 },
+    /// 8XY1 - Set Vx to Vx OR Vy.
+    Or { x: u8, y: u8 },
+    /// 8XY2 - Set Vx to Vx AND Vy.
+    And { x: u8, y: u8 },
+    /// 8XY3 - Set Vx to Vx XOR Vy.
+    Xor { x: u8, y: u8 },
+    /// 8XY4 - Set Vx to Vx + Vy, Vf set on carry.
+    AddReg { x: u8, y: u8 },
+    /// 8XY5 - Set Vx to Vx - Vy, Vf cleared on borrow.
+    Sub { x: u8, y: u8 },
+    /// 8XY6 - Shift right, Vf set to the bit shifted out.
+    Shr { x: u8, y: u8 },
+    /// 8XY7 - Set Vx to Vy - Vx, Vf cleared on borrow.
+    Subn { x: u8, y: u8 },
+    /// 8XYE - Shift left, Vf set to the bit shifted out.
+    Shl { x: u8, y: u8 },
+    /// 9XY0 - Skip next instruction if Vx != Vy.
+    SneReg { x: u8, y: u8 },
+    /// ANNN - Set I to NNN.
+    LdI(u16),
+    /// BNNN - Jump to NNN plus V0.
+    JpV0(u16),
+    /// CXNN - Set Vx to a random byte AND NN.
+    Rnd { x: u8, nn: u8 },
+    /// DXYN - Draw an N byte sprite from I at (Vx, Vy). On SUPER-CHIP
+    /// DXY0 draws a 16x16 sprite instead.
+    Drw { x: u8, y: u8, n: u8 },
+    /// EX9E - Skip next instruction if the key in Vx is pressed.
+    Skp { x: u8 },
+    /// EXA1 - Skip next instruction if the key in Vx is not pressed.
+    Sknp { x: u8 },
+    /// F000 NNNN - Set I to the 16 bit address in the following word. XO-CHIP.
+    ///
+    /// The only four byte instruction; `decode` and `encode` deal with the
+    /// first word alone.
+    LdILong,
+    /// FN01 - Select the bitplanes N that drawing and scrolling affect. XO-CHIP.
+    Plane(u8),
+    /// F002 - Load the 16 byte audio pattern buffer from I. XO-CHIP.
+    Audio,
+    /// FX07 - Set Vx to the delay timer.
+    LdVxDt { x: u8 },
+    /// FX0A - Wait for a key press and store it in Vx.
+    LdVxK { x: u8 },
+    /// FX15 - Set the delay timer to Vx.
+    LdDtVx { x: u8 },
+    /// FX18 - Set the sound timer to Vx.
+    LdStVx { x: u8 },
+    /// FX1E - Add Vx to I.
+    AddI { x: u8 },
+    /// FX29 - Point I at the font sprite for the digit in Vx.
+    LdF { x: u8 },
+    /// FX30 - Point I at the large font sprite for the digit in Vx. SUPER-CHIP.
+    LdHf { x: u8 },
+    /// FX3A - Set the audio pattern playback pitch to Vx. XO-CHIP.
+    Pitch { x: u8 },
+    /// FX33 - Store the BCD representation of Vx at I, I+1 and I+2.
+    LdB { x: u8 },
+    /// FX55 - Store V0 to Vx in memory starting at I.
+    LdIVx { x: u8 },
+    /// FX65 - Fill V0 to Vx from memory starting at I.
+    LdVxI { x: u8 },
+    /// FX75 - Store V0 to Vx in the RPL user flags. SUPER-CHIP.
+    LdRVx { x: u8 },
+    /// FX85 - Fill V0 to Vx from the RPL user flags. SUPER-CHIP.
+    LdVxR { x: u8 },
+}
+
+/// The instruction set extensions, each a superset of the one before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum InstructionSet {
+    /// The original COSMAC VIP instructions.
+    Chip8,
+    SuperChip10,
+    SuperChip11,
+    XoChip,
+}
+
+impl Instruction {
+    /// The first instruction set to include this instruction.
+    pub fn instruction_set(&self) -> InstructionSet {
+        match *self {
+            Instruction::Exit | Instruction::Low | Instruction::High |
+            Instruction::LdHf { .. } | Instruction::LdRVx { .. } | Instruction::LdVxR { .. } =>
+                InstructionSet::SuperChip10,
+            Instruction::Scd(_) | Instruction::Scr | Instruction::Scl =>
+                InstructionSet::SuperChip11,
+            Instruction::SaveRange { .. } | Instruction::LoadRange { .. } | Instruction::LdILong |
+            Instruction::Scu(_) | Instruction::Plane(_) | Instruction::Audio | Instruction::Pitch { .. } =>
+                InstructionSet::XoChip,
+            _ => InstructionSet::Chip8
+        }
+    }
+
+    /// Decodes a raw opcode, returning `None` if it is not a known instruction.
+    ///
+    /// Only `00E0` is `Cls` and only `00EE` is `Ret`. Other `0NNN` opcodes,
+    /// such as `0000`, were once taken as one or the other by their last
+    /// digit, but are unknown, so that decoding and encoding round-trip.
+    pub fn decode(opcode: u16) -> Option<Instruction> {
+        let x   = ((opcode & 0x0F00) >> 8) as u8;
+        let y   = ((opcode & 0x00F0) >> 4) as u8;
+        let n   =  (opcode & 0x000F)       as u8;
+        let nn  =  (opcode & 0x00FF)       as u8;
+        let nnn =   opcode & 0x0FFF;
+
+        // Decode opcode by removing the first nibble to get operation type.
+        let ins = match opcode & 0xF000 {
+            0x0000 => match opcode {
+                0x00E0 => Instruction::Cls,
+                0x00EE => Instruction::Ret,
+                0x00FB => Instruction::Scr,
+                0x00FC => Instruction::Scl,
+                0x00FD => Instruction::Exit,
+                0x00FE => Instruction::Low,
+                0x00FF => Instruction::High,
+                _ if opcode & 0xFFF0 == 0x00C0 => Instruction::Scd(n),
+                _ if opcode & 0xFFF0 == 0x00D0 => Instruction::Scu(n),
+                _      => return None
+            },
+            0x1000 => Instruction::Jp(nnn),
+            0x2000 => Instruction::Call(nnn),
+            0x3000 => Instruction::SeImm { x, nn },
+            0x4000 => Instruction::SneImm { x, nn },
+            0x5000 => match n {
+                0x0 => Instruction::SeReg { x, y },
+                0x2 => Instruction::SaveRange { x, y },
+                0x3 => Instruction::LoadRange { x, y },
+                _   => return None
+            },
+            0x6000 => Instruction::LdImm { x, nn },
+            0x7000 => Instruction::AddImm { x, nn },
+            0x8000 => match n {
+                0x0 => Instruction::LdReg { x, y },
+                0x1 => Instruction::Or { x, y },
+                0x2 => Instruction::And { x, y },
+                0x3 => Instruction::Xor { x, y },
+                0x4 => Instruction::AddReg { x, y },
+                0x5 => Instruction::Sub { x, y },
+                0x6 => Instruction::Shr { x, y },
+                0x7 => Instruction::Subn { x, y },
+                0xE => Instruction::Shl { x, y },
+                _   => return None
+            },
+            0x9000 if n == 0 => Instruction::SneReg { x, y },
+            0xA000 => Instruction::LdI(nnn),
+            0xB000 => Instruction::JpV0(nnn),
+            0xC000 => Instruction::Rnd { x, nn },
+            0xD000 => Instruction::Drw { x, y, n },
+            0xE000 => match nn {
+                0x9E => Instruction::Skp { x },
+                0xA1 => Instruction::Sknp { x },
+                _    => return None
+            },
+            0xF000 => match nn {
+                0x00 if x == 0 => Instruction::LdILong,
+                0x01 => Instruction::Plane(x),
+                0x02 if x == 0 => Instruction::Audio,
+                0x07 => Instruction::LdVxDt { x },
+                0x0A => Instruction::LdVxK { x },
+                0x15 => Instruction::LdDtVx { x },
+                0x18 => Instruction::LdStVx { x },
+                0x1E => Instruction::AddI { x },
+                0x29 => Instruction::LdF { x },
+                0x30 => Instruction::LdHf { x },
+                0x33 => Instruction::LdB { x },
+                0x3A => Instruction::Pitch { x },
+                0x55 => Instruction::LdIVx { x },
+                0x65 => Instruction::LdVxI { x },
+                0x75 => Instruction::LdRVx { x },
+                0x85 => Instruction::LdVxR { x },
+                _    => return None
+            },
+            _ => return None
+        };
+
+        Some(ins)
+    }
+
+    /// Encodes the instruction back into its raw opcode.
+    ///
+    /// `Instruction::decode(ins.encode()) == Some(ins)` for every instruction.
+    pub fn encode(&self) -> u16 {
+        fn xnn(op: u16, x: u8, nn: u8) -> u16 { op | (x as u16) << 8 | nn as u16 }
+        fn xyn(op: u16, x: u8, y: u8, n: u8) -> u16 { op | (x as u16) << 8 | (y as u16) << 4 | n as u16 }
+        fn fx(x: u8, nn: u8) -> u16 { xnn(0xF000, x, nn) }
+
+        match *self {
+            Instruction::Cls              => 0x00E0,
+            Instruction::Ret              => 0x00EE,
+            Instruction::Scd(n)           => 0x00C0 | n as u16,
+            Instruction::Scu(n)           => 0x00D0 | n as u16,
+            Instruction::Scr              => 0x00FB,
+            Instruction::Scl              => 0x00FC,
+            Instruction::Exit             => 0x00FD,
+            Instruction::Low              => 0x00FE,
+            Instruction::High             => 0x00FF,
+            Instruction::Jp(nnn)          => 0x1000 | nnn,
+            Instruction::Call(nnn)        => 0x2000 | nnn,
+            Instruction::SeImm { x, nn }  => xnn(0x3000, x, nn),
+            Instruction::SneImm { x, nn } => xnn(0x4000, x, nn),
+            Instruction::SeReg { x, y }   => xyn(0x5000, x, y, 0x0),
+            Instruction::SaveRange { x, y } => xyn(0x5000, x, y, 0x2),
+            Instruction::LoadRange { x, y } => xyn(0x5000, x, y, 0x3),
+            Instruction::LdImm { x, nn }  => xnn(0x6000, x, nn),
+            Instruction::AddImm { x, nn } => xnn(0x7000, x, nn),
+            Instruction::LdReg { x, y }   => xyn(0x8000, x, y, 0x0),
+            Instruction::Or { x, y }      => xyn(0x8000, x, y, 0x1),
+            Instruction::And { x, y }     => xyn(0x8000, x, y, 0x2),
+            Instruction::Xor { x, y }     => xyn(0x8000, x, y, 0x3),
+            Instruction::AddReg { x, y }  => xyn(0x8000, x, y, 0x4),
+            Instruction::Sub { x, y }     => xyn(0x8000, x, y, 0x5),
+            Instruction::Shr { x, y }     => xyn(0x8000, x, y, 0x6),
+            Instruction::Subn { x, y }    => xyn(0x8000, x, y, 0x7),
+            Instruction::Shl { x, y }     => xyn(0x8000, x, y, 0xE),
+            Instruction::SneReg { x, y }  => xyn(0x9000, x, y, 0x0),
+            Instruction::LdI(nnn)         => 0xA000 | nnn,
+            Instruction::JpV0(nnn)        => 0xB000 | nnn,
+            Instruction::Rnd { x, nn }    => xnn(0xC000, x, nn),
+            Instruction::Drw { x, y, n }  => xyn(0xD000, x, y, n),
+            Instruction::Skp { x }        => xnn(0xE000, x, 0x9E),
+            Instruction::Sknp { x }       => xnn(0xE000, x, 0xA1),
+            Instruction::LdILong          => 0xF000,
+            Instruction::Plane(n)         => fx(n, 0x01),
+            Instruction::Audio            => 0xF002,
+            Instruction::LdVxDt { x }     => fx(x, 0x07),
+            Instruction::LdVxK { x }      => fx(x, 0x0A),
+            Instruction::LdDtVx { x }     => fx(x, 0x15),
+            Instruction::LdStVx { x }     => fx(x, 0x18),
+            Instruction::AddI { x }       => fx(x, 0x1E),
+            Instruction::LdF { x }        => fx(x, 0x29),
+            Instruction::LdHf { x }       => fx(x, 0x30),
+            Instruction::LdB { x }        => fx(x, 0x33),
+            Instruction::Pitch { x }      => fx(x, 0x3A),
+            Instruction::LdIVx { x }      => fx(x, 0x55),
+            Instruction::LdVxI { x }      => fx(x, 0x65),
+            Instruction::LdRVx { x }      => fx(x, 0x75),
+            Instruction::LdVxR { x }      => fx(x, 0x85),
+        }
+    }
+}
+
+/// Formats the instruction in Cowgod's assembly syntax, such as
+/// `LD V3, 0x10` or `DRW V0, V1, 5`.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Instruction::Cls                 => write!(f, "CLS"),
+            Instruction::Ret                 => write!(f, "RET"),
+            Instruction::Scd(n)              => write!(f, "SCD {}", n),
+            Instruction::Scu(n)              => write!(f, "SCU {}", n),
+            Instruction::Scr                 => write!(f, "SCR"),
+            Instruction::Scl                 => write!(f, "SCL"),
+            Instruction::Exit                => write!(f, "EXIT"),
+            Instruction::Low                 => write!(f, "LOW"),
+            Instruction::High                => write!(f, "HIGH"),
+            Instruction::Jp(nnn)             => write!(f, "JP 0x{:03X}", nnn),
+            Instruction::Call(nnn)           => write!(f, "CALL 0x{:03X}", nnn),
+            Instruction::SeImm { x, nn }     => write!(f, "SE V{:X}, 0x{:02X}", x, nn),
+            Instruction::SneImm { x, nn }    => write!(f, "SNE V{:X}, 0x{:02X}", x, nn),
+            Instruction::SeReg { x, y }      => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::SaveRange { x, y }  => write!(f, "SAVE V{:X} - V{:X}", x, y),
+            Instruction::LoadRange { x, y }  => write!(f, "LOAD V{:X} - V{:X}", x, y),
+            Instruction::LdImm { x, nn }     => write!(f, "LD V{:X}, 0x{:02X}", x, nn),
+            Instruction::AddImm { x, nn }    => write!(f, "ADD V{:X}, 0x{:02X}", x, nn),
+            Instruction::LdReg { x, y }      => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::Or { x, y }         => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::And { x, y }        => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::Xor { x, y }        => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::AddReg { x, y }     => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub { x, y }        => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::Shr { x, y }        => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::Subn { x, y }       => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::Shl { x, y }        => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SneReg { x, y }     => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::LdI(nnn)            => write!(f, "LD I, 0x{:03X}", nnn),
+            Instruction::JpV0(nnn)           => write!(f, "JP V0, 0x{:03X}", nnn),
+            Instruction::Rnd { x, nn }       => write!(f, "RND V{:X}, 0x{:02X}", x, nn),
+            Instruction::Drw { x, y, n }     => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::Skp { x }           => write!(f, "SKP V{:X}", x),
+            Instruction::Sknp { x }          => write!(f, "SKNP V{:X}", x),
+            Instruction::LdILong             => write!(f, "LD I, LONG"),
+            Instruction::Plane(n)            => write!(f, "PLANE {}", n),
+            Instruction::Audio               => write!(f, "AUDIO"),
+            Instruction::LdVxDt { x }        => write!(f, "LD V{:X}, DT", x),
+            Instruction::LdVxK { x }         => write!(f, "LD V{:X}, K", x),
+            Instruction::LdDtVx { x }        => write!(f, "LD DT, V{:X}", x),
+            Instruction::LdStVx { x }        => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddI { x }          => write!(f, "ADD I, V{:X}", x),
+            Instruction::LdF { x }           => write!(f, "LD F, V{:X}", x),
+            Instruction::LdHf { x }          => write!(f, "LD HF, V{:X}", x),
+            Instruction::Pitch { x }         => write!(f, "PITCH V{:X}", x),
+            Instruction::LdB { x }           => write!(f, "LD B, V{:X}", x),
+            Instruction::LdIVx { x }         => write!(f, "LD [I], V{:X}", x),
+            Instruction::LdVxI { x }         => write!(f, "LD V{:X}, [I]", x),
+            Instruction::LdRVx { x }         => write!(f, "LD R, V{:X}", x),
+            Instruction::LdVxR { x }         => write!(f, "LD V{:X}, R", x),
+        }
+    }
+}
+
+#[test]
+fn test_decode_encode_round_trip() {
+    let mut known = 0;
+    for opcode in 0..=0xFFFF_u16 {
+        if let Some(ins) = Instruction::decode(opcode) {
+            assert_eq!(ins.encode(), opcode, "{:?} did not encode back to 0x{:04X}", ins, opcode);
+            known += 1;
+        }
+    }
+    // 9 fixed, 00CN, 00DN, FN01, 4 with a 12 bit address, 5 with an 8 bit
+    // immediate, 13 register pair ops, DXYN and 15 single register ops.
+    assert_eq!(known, 9 + 0x10 + 0x10 + 0x10 + 4 * 0x1000 + 5 * 0x1000 + 13 * 0x100 + 0x1000 + 15 * 0x10);
+}
+
+#[test]
+fn test_decode() {
+    assert_eq!(Instruction::decode(0x00E0), Some(Instruction::Cls));
+    assert_eq!(Instruction::decode(0x1A2A), Some(Instruction::Jp(0xA2A)));
+    assert_eq!(Instruction::decode(0xD125), Some(Instruction::Drw { x: 1, y: 2, n: 5 }));
+    assert_eq!(Instruction::decode(0x8AB6), Some(Instruction::Shr { x: 0xA, y: 0xB }));
+    assert_eq!(Instruction::decode(0x00C4), Some(Instruction::Scd(4)));
+    assert_eq!(Instruction::decode(0x00D3), Some(Instruction::Scu(3)));
+    assert_eq!(Instruction::Scu(3).instruction_set(), InstructionSet::XoChip);
+    assert_eq!(Instruction::decode(0xF330), Some(Instruction::LdHf { x: 3 }));
+    assert_eq!(Instruction::decode(0x0000), None);
+    assert_eq!(Instruction::decode(0x5123), Some(Instruction::LoadRange { x: 1, y: 2 }));
+    assert_eq!(Instruction::decode(0xF000), Some(Instruction::LdILong));
+    assert_eq!(Instruction::decode(0xF100), None);
+    assert_eq!(Instruction::decode(0x5121), None);
+    assert_eq!(Instruction::decode(0xE19F), None);
+}
+
+#[test]
+fn test_display() {
+    assert_eq!(Instruction::LdImm { x: 3, nn: 0x10 }.to_string(), "LD V3, 0x10");
+    assert_eq!(Instruction::Drw { x: 0, y: 1, n: 5 }.to_string(), "DRW V0, V1, 5");
+    assert_eq!(Instruction::Jp(0x200).to_string(), "JP 0x200");
+    assert_eq!(Instruction::LdIVx { x: 0xF }.to_string(), "LD [I], VF");
+}
@@ -0,0 +1,227 @@
+//! Which keyboard keys and controller buttons press which of the 16 hex
+//! keys.
+//!
+//! Keys are named as SDL names them: `"1"`, `"Q"`, `"Keypad 7"`, `"Up"`,
+//! so a frontend can look them up with `SDL_GetKeyFromName`. Controller
+//! buttons use SDL's game controller names, `"a"`, `"dpup"`,
+//! `"leftshoulder"`, and stick directions are an axis name and a sign:
+//! `"leftx-"` is the left stick pushed left. A keymap starts from a preset
+//! and, for a few well known games, buttons chosen for them, and may be
+//! changed by a TOML config file:
+//!
+//! ```toml
+//! # The layout for every ROM: "vip" (the default), "hex" or "numpad".
+//! keymap = "vip"
+//!
+//! # Individual hex keys to move, on top of the layout.
+//! [keys]
+//! 5 = "Up"
+//!
+//! # Controller buttons and the hex keys they press. "none" unbinds one.
+//! [buttons]
+//! a = "5"
+//! back = "none"
+//!
+//! # Settings for one ROM, by file name, on top of those above.
+//! [rom."pong.ch8"]
+//! keymap = "hex"
+//! keys = { 1 = "W", 4 = "S", c = "Up", d = "Down" }
+//! buttons = { dpup = "1", dpdown = "4", y = "c", a = "d" }
+//! ```
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// Controller buttons for every ROM: the d-pad and left stick on 2, 4, 6
+/// and 8, which most games steer with, and the face buttons on 5, 0, 1
+/// and 3 for everything else.
+const DEFAULT_BUTTONS: [(&str, usize); 12] = [
+    ("dpup", 0x2), ("dpdown", 0x8), ("dpleft", 0x4), ("dpright", 0x6),
+    ("lefty-", 0x2), ("lefty+", 0x8), ("leftx-", 0x4), ("leftx+", 0x6),
+    ("a", 0x5), ("b", 0x0), ("x", 0x1), ("y", 0x3)
+];
+
+/// Controller buttons for well known games, by the start of their file
+/// names, on top of `DEFAULT_BUTTONS`. Games such as Brix and Space
+/// Invaders, which move with 4 and 6 and fire with 5, need nothing more.
+const GAME_BUTTONS: [(&str, &[(&str, usize)]); 2] = [
+    // Player one on the d-pad and left stick, player two on the right
+    // stick or the face buttons.
+    ("pong", &[("dpup", 0x1), ("dpdown", 0x4), ("lefty-", 0x1), ("lefty+", 0x4),
+               ("righty-", 0xC), ("righty+", 0xD), ("y", 0xC), ("a", 0xD)]),
+    // Move with 5 and 6, drop with 7 and rotate with 4.
+    ("tetris", &[("dpleft", 0x5), ("dpright", 0x6), ("dpdown", 0x7), ("leftx-", 0x5),
+                 ("leftx+", 0x6), ("lefty+", 0x7), ("a", 0x4), ("dpup", 0x4)])
+];
+
+/// A layout of all 16 hex keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Preset {
+    /// The COSMAC VIP's 4x4 keypad on the left of the keyboard:
+    ///
+    /// ```text
+    /// 1 2 3 C        1 2 3 4
+    /// 4 5 6 D   ->   Q W E R
+    /// 7 8 9 E        A S D F
+    /// A 0 B F        Z X C V
+    /// ```
+    #[default]
+    Vip,
+    /// Each hex key on the keyboard key of the same name.
+    Hex,
+    /// Digits on the numeric keypad, and A to F on `/ * - + Enter .`.
+    Numpad
+}
+
+impl Preset {
+    /// The key names for hex keys 0 to F.
+    pub fn keys(&self) -> [&'static str; 16] {
+        match *self {
+            Preset::Vip => ["X", "1", "2", "3", "Q", "W", "E", "A",
+                            "S", "D", "Z", "C", "4", "R", "F", "V"],
+            Preset::Hex => ["0", "1", "2", "3", "4", "5", "6", "7",
+                            "8", "9", "A", "B", "C", "D", "E", "F"],
+            Preset::Numpad => ["Keypad 0", "Keypad 1", "Keypad 2", "Keypad 3",
+                               "Keypad 4", "Keypad 5", "Keypad 6", "Keypad 7",
+                               "Keypad 8", "Keypad 9", "Keypad /", "Keypad *",
+                               "Keypad -", "Keypad +", "Keypad Enter", "Keypad ."]
+        }
+    }
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Preset, String> {
+        match s {
+            "vip"    => Ok(Preset::Vip),
+            "hex"    => Ok(Preset::Hex),
+            "numpad" => Ok(Preset::Numpad),
+            _        => Err(format!("Unknown keymap: {} (expected vip, hex or numpad)", s))
+        }
+    }
+}
+
+/// The key names for hex keys 0 to F, and the hex key each controller
+/// button presses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    pub keys: [String; 16],
+    pub buttons: BTreeMap<String, usize>
+}
+
+impl Keymap {
+    pub fn preset(preset: Preset) -> Keymap {
+        Keymap {
+            keys: preset.keys().map(String::from),
+            buttons: DEFAULT_BUTTONS.iter().map(|&(button, key)| (button.to_string(), key)).collect()
+        }
+    }
+
+    /// The keymap a config file gives the ROM named `rom`: the file's
+    /// layout, keys and buttons, then the ROM's own, each applied over
+    /// the last. `preset`, if given, replaces both layouts.
+    pub fn from_config(config: &str, rom: &str, preset: Option<Preset>) -> Result<Keymap, String> {
+        let config: toml::Value = config.parse().map_err(|err: toml::de::Error| err.to_string())?;
+        let mut tables = vec![config.as_table()];
+        if let Some(roms) = config.get("rom") {
+            tables.push(roms.get(rom).and_then(toml::Value::as_table));
+        }
+
+        let mut keymap = Keymap::preset(preset.unwrap_or_default());
+        let name = rom.to_lowercase();
+        if let Some(&(_, buttons)) = GAME_BUTTONS.iter().find(|&&(game, _)| name.starts_with(game)) {
+            keymap.buttons.extend(buttons.iter().map(|&(button, key)| (button.to_string(), key)));
+        }
+        for table in tables.into_iter().flatten() {
+            if let (None, Some(layout)) = (preset, table.get("keymap")) {
+                let layout = layout.as_str().ok_or("keymap must be a string")?;
+                keymap.keys = Keymap::preset(layout.parse()?).keys;
+            }
+            if let Some(buttons) = table.get("buttons") {
+                let buttons = buttons.as_table().ok_or("buttons must be a table")?;
+                for (button, key) in buttons.iter() {
+                    match key.as_str() {
+                        Some("none") => {
+                            keymap.buttons.remove(button);
+                        },
+                        Some(key) => {
+                            keymap.buttons.insert(button.clone(), hex_key(key)?);
+                        },
+                        None => return Err(format!("Button {} must be a hex key", button))
+                    }
+                }
+            }
+            let keys = match table.get("keys") {
+                Some(keys) => keys.as_table().ok_or("keys must be a table")?,
+                None => continue
+            };
+            for (hex, name) in keys.iter() {
+                let name = name.as_str().ok_or_else(|| format!("Key {} must be a key name", hex))?;
+                keymap.keys[hex_key(hex)?] = name.to_string();
+            }
+        }
+        Ok(keymap)
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        Keymap::preset(Preset::default())
+    }
+}
+
+fn hex_key(hex: &str) -> Result<usize, String> {
+    match usize::from_str_radix(hex, 16) {
+        Ok(n) if n < 16 => Ok(n),
+        _ => Err(format!("Not a hex key: {}", hex))
+    }
+}
+
+#[test]
+fn test_keymap_config() {
+    let config = "
+        keymap = \"hex\"
+        [keys]
+        5 = \"Up\"
+        [rom.\"pong.ch8\"]
+        keymap = \"vip\"
+        keys = { c = \"Down\" }
+    ";
+    let keymap = Keymap::from_config(config, "brix.ch8", None).unwrap();
+    assert_eq!(keymap.keys[5], "Up");
+    assert_eq!(keymap.keys[0xA], "A");
+
+    // The ROM's layout replaces the file's, keys and all.
+    let keymap = Keymap::from_config(config, "pong.ch8", None).unwrap();
+    assert_eq!(keymap.keys[5], "W");
+    assert_eq!(keymap.keys[0xC], "Down");
+
+    // A preset from the command line beats both layouts, but not the keys.
+    let keymap = Keymap::from_config(config, "pong.ch8", Some(Preset::Numpad)).unwrap();
+    assert_eq!((&*keymap.keys[5], &*keymap.keys[0xC], &*keymap.keys[1]), ("Up", "Down", "Keypad 1"));
+
+    assert_eq!(Keymap::from_config("", "brix.ch8", None).unwrap(), Keymap::default());
+    assert_eq!(Keymap::from_config("[keys]\n10 = \"Q\"", "", None).unwrap_err(), "Not a hex key: 10");
+    assert!(Keymap::from_config("keymap = \"dvorak\"", "", None).is_err());
+}
+
+#[test]
+fn test_button_config() {
+    // Pong's paddles are on 1/4 and C/D, not the usual 2/8.
+    let keymap = Keymap::from_config("", "PONG2.ch8", None).unwrap();
+    assert_eq!((keymap.buttons["dpup"], keymap.buttons["righty+"], keymap.buttons["b"]), (0x1, 0xD, 0x0));
+
+    let config = "
+        [buttons]
+        b = \"none\"
+        start = \"f\"
+        [rom.\"pong.ch8\"]
+        buttons = { dpup = \"2\" }
+    ";
+    let keymap = Keymap::from_config(config, "pong.ch8", None).unwrap();
+    assert_eq!((keymap.buttons["dpup"], keymap.buttons["dpdown"], keymap.buttons["start"]), (0x2, 0x4, 0xF));
+    assert!(!keymap.buttons.contains_key("b"));
+
+    assert!(Keymap::from_config("[buttons]\na = 5", "", None).is_err());
+}
@@ -0,0 +1,55 @@
+//! A CHIP-8 interpreter core.
+//!
+//! The interpreter has no knowledge of windows, audio or input devices;
+//! a frontend owns a `Chip8`, feeds it key state, runs it a frame at a
+//! time through a `Scheduler`, presents `gfx` however it likes and sounds
+//! the buzzer when `take_sound_events` says so.
+
+extern crate gif;
+extern crate png;
+extern crate rand;
+extern crate toml;
+
+mod assembler;
+mod capture;
+mod audio;
+mod debugger;
+mod disasm;
+mod error;
+mod hash;
+mod instruction;
+mod keymap;
+mod machine;
+mod movie;
+mod palette;
+mod phosphor;
+mod platform;
+mod quirks;
+mod random;
+mod rewind;
+mod scheduler;
+mod screen;
+mod script;
+mod state;
+
+pub use assembler::{assemble, AsmError};
+pub use capture::Capture;
+pub use audio::{Pattern, Tone, Waveform};
+pub use debugger::{disassemble, Debugger};
+pub use disasm::disassemble_rom;
+pub use error::Chip8Error;
+pub use hash::fnv1a;
+pub use instruction::{Instruction, InstructionSet};
+pub use keymap::{Keymap, Preset};
+pub use machine::{Chip8, SoundEvent, StepOutcome, BIG_FONTSET, CHIP8_FONTSET};
+pub use movie::{Movie, MOVIE_VERSION};
+pub use palette::{Colour, Palette};
+pub use phosphor::Phosphor;
+pub use platform::Platform;
+pub use quirks::Quirks;
+pub use random::{Generator, Random, VipRandom, Xorshift};
+pub use rewind::Rewind;
+pub use scheduler::{Scheduler, TIMER_HZ};
+pub use screen::{screen_text, timestamped_path, write_png};
+pub use script::InputScript;
+pub use state::STATE_VERSION;
@@ -0,0 +1,1444 @@
+use std::io::prelude::*;
+use std::io;
+use std::fs::File;
+use std::ops::Range;
+use std::cmp;
+use audio::Pattern;
+use error::Chip8Error;
+use instruction::{Instruction, InstructionSet};
+use platform::Platform;
+use quirks::Quirks;
+use random::{Generator, Random};
+
+/// The built-in hexadecimal font, loaded into the first 80 bytes of memory.
+pub const CHIP8_FONTSET: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80  // F
+];
+
+/// The SUPER-CHIP 8x10 font for FX30, loaded into memory just after
+/// CHIP8_FONTSET. SUPER-CHIP only defined 0-9; A-F are Octo's.
+pub const BIG_FONTSET: [u8; 160] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+    0x18, 0x3C, 0x66, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFE, 0xC3, 0xC3, 0xFE, 0xFE, 0xC3, 0xC3, 0xFE, 0xFC, // B
+    0x3C, 0x7E, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0x7E, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFC, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFC, 0xC0, 0xC0, 0xC0, 0xC0  // F
+];
+
+/// Where BIG_FONTSET is loaded.
+const BIG_FONTSET_ADDR: usize = 0x50;
+
+/// The registers from Vx to Vy inclusive, counting down if x > y.
+fn register_range(x: u8, y: u8) -> Vec<usize> {
+    let (x, y) = (x as usize, y as usize);
+    if x <= y { (x..=y).collect() } else { (y..=x).rev().collect() }
+}
+
+/// What happened as the result of executing a single instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// The instruction completed and the program counter has moved on.
+    Continue,
+    /// `FX0A` is blocked waiting for a key; the same instruction will run again.
+    WaitingForKey,
+    /// A sprite was drawn with the display wait quirk on; no more
+    /// instructions should run until the next frame.
+    WaitingForVBlank,
+    /// The program executed `00FD`. The same instruction will run again,
+    /// so the machine stays stopped.
+    Exit,
+}
+
+/// A change in whether the sound timer is sounding the buzzer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoundEvent {
+    /// The sound timer was set to a non-zero value while silent.
+    Start,
+    /// The sound timer reached zero, or was set to zero, while sounding.
+    Stop,
+}
+
+pub struct Chip8 {
+    pub opcode:      u16,           // The current opcode.
+    pub memory:      Vec<u8>,       // Chip8 memory, sized by the platform.
+    pub v:           [u8; 16],      // General purpose registers.
+    pub i:           u16,           // Index register.
+    pub pc:          u16,           // Program counter.
+    pub gfx:         Vec<u8>,       // Pixel data, one byte per pixel of the current resolution
+                                    // holding a bitmask of the planes lit there.
+    pub hires:       bool,          // Whether the 128x64 SUPER-CHIP display is in use.
+    pub planes:      u8,            // XO-CHIP bitplanes selected for drawing.
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub audio_pattern: [u8; 16],    // XO-CHIP 1 bit audio samples, most significant bit first.
+    pub pitch:       u8,            // XO-CHIP audio pattern playback pitch.
+    pub stack:       [u16; 16],     // Stack used to remember location before a jump.
+    pub sp:          u16,           // Stack pointer.
+    pub key:         [u8; 16],
+    pub rpl:         [u8; 16],      // SUPER-CHIP RPL user flags.
+    pub draw_flag:   bool,          // Set when gfx changes; cleared by the frontend once shown.
+    pub platform:    Platform,
+    pub quirks:      Quirks,        // Defaults to the platform's quirks.
+    pub(crate) sound_events: Vec<SoundEvent>, // Buzzer changes not yet taken by the frontend.
+    pub rng:         Box<dyn Random>, // Source of CXNN's random numbers.
+    last_write:      Option<Range<usize>> // Memory written by the last instruction.
+}
+
+impl Default for Chip8 {
+    fn default() -> Chip8 {
+        Chip8::new(Platform::default())
+    }
+}
+
+impl Chip8 {
+    /// Constructs a new 'Chip8' configured for `platform`.
+    ///
+    /// Sets all integer fields and arrays to zero, points the
+    /// program counter at 0x200 and loads the fontset.
+    pub fn new(platform: Platform) -> Chip8 {
+        let mut c8 = Chip8 {
+            opcode:      0,
+            memory:      vec![0_u8; platform.memory_size()],
+            v:           [0_u8; 16],
+            i:           0,
+            pc:          0x200,
+            gfx:         vec![0_u8; 64*32],
+            hires:       false,
+            planes:      1,
+            delay_timer: 0,
+            sound_timer: 0,
+            audio_pattern: [0_u8; 16],
+            pitch:       64,
+            stack:       [0_u16; 16],
+            sp:          0,
+            key:         [0_u8; 16],
+            rpl:         [0_u8; 16],
+            draw_flag:   false,
+            platform,
+            quirks:      platform.quirks(),
+            sound_events: Vec::new(),
+            rng:         Generator::default().create(rand::random()),
+            last_write:  None
+        };
+
+        c8.load_fontset();
+        c8
+    }
+
+    /// Reseeds the random number generator used by CXNN, so that a run
+    /// can be repeated exactly.
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng.reseed(seed);
+    }
+
+    /// Loads the contents of CHIP8_FONTSET into the first
+    /// 80 bytes of chip8 memory, followed by BIG_FONTSET.
+    fn load_fontset(&mut self) {
+        self.memory[..CHIP8_FONTSET.len()].copy_from_slice(&CHIP8_FONTSET);
+        self.memory[BIG_FONTSET_ADDR..BIG_FONTSET_ADDR + BIG_FONTSET.len()].copy_from_slice(&BIG_FONTSET);
+    }
+
+    /// The width and height of the display at the current resolution.
+    pub fn display_size(&self) -> (usize, usize) {
+        if self.hires { (128, 64) } else { (64, 32) }
+    }
+
+    /// Switches between the low and high resolution displays, clearing the screen.
+    pub(crate) fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        let (width, height) = self.display_size();
+        self.gfx = vec![0; width * height];
+        self.draw_flag = true;
+    }
+
+    /// Loads a ROM from disk into memory at 0x200.
+    ///
+    /// Anything that does not fit in the platform's memory is ignored.
+    pub fn load_game(&mut self, filename: &str) -> Result<(), io::Error> {
+        // Total available memory minus that used by the system (512)
+        let available = self.memory.len() - 0x200;
+        let mut buffer = Vec::with_capacity(available);
+        let file = File::open(filename)?;
+        file.take(available as u64).read_to_end(&mut buffer)?;
+
+        self.load_rom(&buffer);
+        Ok(())
+    }
+
+    /// Copies a ROM into memory at 0x200, as much as fits.
+    pub fn load_rom(&mut self, rom: &[u8]) {
+        let len = rom.len().min(self.memory.len() - 0x200);
+        self.memory[0x200..0x200 + len].copy_from_slice(&rom[..len]);
+    }
+
+    /// Fetch the current opcode from memory and set self.opcode.
+    ///
+    /// Fetches the 16 bit opcode from two sequential 8 bit locations
+    /// in memory pointed to by self.pc, then combines them by shifting
+    /// the first byte back by 8 bits and ORing by the second byte.
+    pub fn fetch(&mut self) -> Result<(), Chip8Error> {
+        let pc = self.pc as usize;
+        let bytes = self.mem_range(pc, 2)?;
+        self.opcode = (self.memory[bytes.start] as u16) << 8 | self.memory[bytes.start + 1] as u16;
+        Ok(())
+    }
+
+    /// Fetches and executes a single instruction.
+    pub fn step(&mut self) -> Result<StepOutcome, Chip8Error> {
+        self.fetch()?;
+        self.execute()
+    }
+
+    /// Decodes and executes self.opcode.
+    ///
+    /// On error the program counter is left at the faulting instruction.
+    /// The program counter and I wrap around the top of the 16 bit
+    /// address space, as they do on XO-CHIP.
+    pub fn execute(&mut self) -> Result<StepOutcome, Chip8Error> {
+        let pc = self.pc;
+        let decoded = Instruction::decode(self.opcode);
+        self.pc = self.pc.wrapping_add(2);
+
+        let outcome = match decoded {
+            Some(ins) if ins.instruction_set() <= self.platform.instruction_set() => self.execute_instruction(ins),
+            _ => Err(Chip8Error::UnknownOpcode { pc, opcode: self.opcode })
+        };
+        if outcome.is_err() {
+            self.pc = pc;
+        }
+
+        outcome
+    }
+
+    /// The memory addresses written by the last instruction executed, if any.
+    pub fn last_write(&self) -> Option<Range<usize>> {
+        self.last_write.clone()
+    }
+
+    /// Decrements the delay and sound timers, and lets the random number
+    /// generator know a frame has passed.
+    ///
+    /// Both timers count down at 60 Hz regardless of how fast
+    /// instructions are executed, so this should be called once
+    /// per 60 Hz frame.
+    pub fn tick_timers(&mut self) {
+        self.rng.tick();
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+
+        if self.sound_timer > 0 {
+            let value = self.sound_timer - 1;
+            self.set_sound_timer(value);
+        }
+    }
+
+    /// Sets the sound timer, recording a `SoundEvent` if the buzzer
+    /// starts or stops as a result.
+    pub(crate) fn set_sound_timer(&mut self, value: u8) {
+        if self.sound_timer == 0 && value > 0 {
+            self.sound_events.push(SoundEvent::Start);
+        } else if self.sound_timer > 0 && value == 0 {
+            self.sound_events.push(SoundEvent::Stop);
+        }
+        self.sound_timer = value;
+    }
+
+    /// Takes the buzzer changes since the last call, oldest first.
+    pub fn take_sound_events(&mut self) -> Vec<SoundEvent> {
+        std::mem::take(&mut self.sound_events)
+    }
+
+    /// The audio pattern the buzzer plays in place of its tone, if the
+    /// platform is XO-CHIP and the program has loaded one.
+    pub fn audio_pattern(&self) -> Option<Pattern> {
+        if self.platform != Platform::XoChip || self.audio_pattern.iter().all(|&byte| byte == 0) {
+            return None;
+        }
+        Some(Pattern { bits: self.audio_pattern, pitch: self.pitch })
+    }
+
+    /// Checks that `len` bytes starting at `start` lie within memory.
+    fn mem_range(&self, start: usize, len: usize) -> Result<Range<usize>, Chip8Error> {
+        if start + len > self.memory.len() {
+            return Err(Chip8Error::MemoryOutOfBounds { addr: cmp::max(start, self.memory.len()) });
+        }
+        Ok(start..start + len)
+    }
+
+    /// Skips the next instruction. On XO-CHIP that may be the four byte
+    /// `F000 NNNN`.
+    fn skip(&mut self) {
+        let long = self.platform.instruction_set() >= InstructionSet::XoChip
+            && self.memory.get(self.pc as usize) == Some(&0xF0)
+            && self.memory.get(self.pc as usize + 1) == Some(&0x00);
+        self.pc = self.pc.wrapping_add(if long { 4 } else { 2 });
+    }
+
+    /// Moves the selected planes of the display by (dx, dy) pixels.
+    /// Pixels moved off the edge are lost; those moved in are blank.
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = self.display_size();
+        let old = self.gfx.clone();
+
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = (x as isize - dx, y as isize - dy);
+                let src = if sx >= 0 && sy >= 0 && (sx as usize) < width && (sy as usize) < height {
+                    old[sy as usize * width + sx as usize]
+                } else {
+                    0
+                };
+                let pixel = &mut self.gfx[y * width + x];
+                *pixel = (*pixel & !self.planes) | (src & self.planes);
+            }
+        }
+
+        self.draw_flag = true;
+    }
+
+    /// The register shifted by 8XY6/8XYE.
+    fn shift_source(&self, x: u8, y: u8) -> u8 {
+        if self.quirks.shift_uses_vy { self.v[y as usize] } else { self.v[x as usize] }
+    }
+
+    /// Executes an already decoded instruction.
+    ///
+    /// The program counter is expected to already point past the
+    /// instruction, as it does when called from `execute`.
+    pub fn execute_instruction(&mut self, ins: Instruction) -> Result<StepOutcome, Chip8Error> {
+        self.last_write = None;
+        match ins {
+            // Clear the screen.
+            Instruction::Cls => {
+                for elem in self.gfx.iter_mut() { *elem &= !self.planes; };
+                self.draw_flag = true;
+            },
+            // Return from subroutine.
+            Instruction::Ret => {
+                if self.sp == 0 {
+                    return Err(Chip8Error::StackUnderflow);
+                }
+                self.sp -= 1;
+                self.pc = self.stack[self.sp as usize];
+            },
+            // Scroll the display down N pixels.
+            Instruction::Scd(n) => {
+                self.scroll(0, n as isize);
+            },
+            // Scroll the display up N pixels.
+            Instruction::Scu(n) => {
+                self.scroll(0, -(n as isize));
+            },
+            // Scroll the display right 4 pixels.
+            Instruction::Scr => {
+                self.scroll(4, 0);
+            },
+            // Scroll the display left 4 pixels.
+            Instruction::Scl => {
+                self.scroll(-4, 0);
+            },
+            // Stop the program.
+            Instruction::Exit => {
+                self.pc = self.pc.wrapping_sub(2);
+                return Ok(StepOutcome::Exit);
+            },
+            // Switch to the low resolution display.
+            Instruction::Low => {
+                self.set_hires(false);
+            },
+            // Switch to the high resolution display.
+            Instruction::High => {
+                self.set_hires(true);
+            },
+            // Jump to address NNN.
+            Instruction::Jp(nnn) => {
+                self.pc = nnn;
+            },
+            // Call subroutine.
+            Instruction::Call(nnn) => {
+                if self.sp as usize >= self.stack.len() {
+                    return Err(Chip8Error::StackOverflow);
+                }
+                self.stack[self.sp as usize] = self.pc;
+                self.pc = nnn;
+                self.sp += 1;
+            },
+            // If Vx == NN skip next instruction.
+            Instruction::SeImm { x, nn } => {
+                if self.v[x as usize] == nn {
+                    self.skip();
+                }
+            },
+            // If Vx != NN skip next instruction.
+            Instruction::SneImm { x, nn } => {
+                if self.v[x as usize] != nn {
+                    self.skip();
+                }
+            },
+            // If Vx == Vy skip next instruction.
+            Instruction::SeReg { x, y } => {
+                if self.v[x as usize] == self.v[y as usize] {
+                    self.skip();
+                }
+            },
+            // Store Vx to Vy in memory starting at address i.
+            Instruction::SaveRange { x, y } => {
+                let regs = register_range(x, y);
+                let dest = self.mem_range(self.i as usize, regs.len())?;
+                self.last_write = Some(dest.clone());
+                for (addr, r) in dest.zip(regs) {
+                    self.memory[addr] = self.v[r];
+                }
+            },
+            // Fill Vx to Vy with values from memory starting at address i.
+            Instruction::LoadRange { x, y } => {
+                let regs = register_range(x, y);
+                let src = self.mem_range(self.i as usize, regs.len())?;
+                for (addr, r) in src.zip(regs) {
+                    self.v[r] = self.memory[addr];
+                }
+            },
+            // Set Vx == NN
+            Instruction::LdImm { x, nn } => {
+                self.v[x as usize] = nn;
+            },
+            // Add NN to Vx (Carry flag is not changed)
+            Instruction::AddImm { x, nn } => {
+                self.v[x as usize] = self.v[x as usize].wrapping_add(nn);
+            },
+            // Set Vx to Vy
+            Instruction::LdReg { x, y } => {
+                self.v[x as usize] = self.v[y as usize];
+            },
+            // Set Vx to Vx OR Vy
+            Instruction::Or { x, y } => {
+                self.v[x as usize] |= self.v[y as usize];
+                if self.quirks.vf_reset {
+                    self.v[15] = 0;
+                }
+            },
+            // Set Vx to Vx AND Vy
+            Instruction::And { x, y } => {
+                self.v[x as usize] &= self.v[y as usize];
+                if self.quirks.vf_reset {
+                    self.v[15] = 0;
+                }
+            },
+            // Set Vx to Vx XOR Vy
+            Instruction::Xor { x, y } => {
+                self.v[x as usize] ^= self.v[y as usize];
+                if self.quirks.vf_reset {
+                    self.v[15] = 0;
+                }
+            },
+            // Set Vx to Vx + Vy (Vf is set to 1 on carry)
+            Instruction::AddReg { x, y } => {
+                let (total, carry) = self.v[x as usize].overflowing_add(self.v[y as usize]);
+                self.v[x as usize] = total;
+                self.v[15] = carry as u8;
+            },
+            // Set Vx to Vx - Vy (Vf is set to 0 on borrow)
+            Instruction::Sub { x, y } => {
+                let (total, borrow) = self.v[x as usize].overflowing_sub(self.v[y as usize]);
+                self.v[x as usize] = total;
+                self.v[15] = !borrow as u8;
+            },
+            // Set Vf to least significant bit of Vx and shift Vx right
+            Instruction::Shr { x, y } => {
+                let src = self.shift_source(x, y);
+                self.v[x as usize] = src >> 1;
+                self.v[15] = src & 0x1;
+            },
+            // Sets Vx to Vy - Vx. Vf is set to 0 on borrow.
+            Instruction::Subn { x, y } => {
+                let (total, borrow) = self.v[y as usize].overflowing_sub(self.v[x as usize]);
+                self.v[x as usize] = total;
+                self.v[15] = !borrow as u8;
+            },
+            // Set Vf to most significant bit of Vx and shift Vx left
+            Instruction::Shl { x, y } => {
+                let src = self.shift_source(x, y);
+                self.v[x as usize] = src << 1;
+                self.v[15] = (src & 0x80) >> 7;
+            },
+            // If Vx != Vy skip next instruction.
+            Instruction::SneReg { x, y } => {
+                if self.v[x as usize] != self.v[y as usize] {
+                    self.skip();
+                }
+            },
+            // Sets I to the address NNN.
+            Instruction::LdI(nnn) => {
+                self.i = nnn;
+            },
+            // Jumps to the address NNN plus V0, or XNN plus Vx.
+            Instruction::JpV0(nnn) => {
+                let offset = if self.quirks.jump_uses_vx { (nnn >> 8) as usize } else { 0 };
+                self.pc = nnn + self.v[offset] as u16;
+            },
+            // Sets VX to the result a random u8 AND NN
+            Instruction::Rnd { x, nn } => {
+                self.v[x as usize] = self.rng.next_byte() & nn;
+            },
+            // Draw a sprite at Vx, Vy, with a width of 8 and height N,
+            // or 16x16 for DXY0 on SUPER-CHIP.
+            Instruction::Drw { x, y, n } => {
+                let (sprite_width, sprite_height) =
+                    if n == 0 && self.platform.instruction_set() >= InstructionSet::SuperChip10 {
+                        (16, 16)
+                    } else {
+                        (8, n as usize)
+                    };
+                let row_bytes = sprite_width / 8;
+                let sprite_bytes = sprite_height * row_bytes;
+                let planes: Vec<u8> = [1_u8, 2].iter().cloned().filter(|p| self.planes & p != 0).collect();
+                let sprite = self.mem_range(self.i as usize, sprite_bytes * planes.len())?;
+
+                // The starting position always wraps; the quirk decides
+                // whether the rest of the sprite wraps or is clipped.
+                let (width, height) = self.display_size();
+                let x0 = self.v[x as usize] as usize % width;
+                let y0 = self.v[y as usize] as usize % height;
+                self.v[15] = 0;
+
+                // With both planes selected the sprite data for plane two
+                // follows that for plane one.
+                for (p, &plane) in planes.iter().enumerate() {
+                    let data = sprite.start + p * sprite_bytes;
+
+                    for h in 0..sprite_height {
+                        if self.quirks.clip_sprites && y0 + h >= height {
+                            break;
+                        }
+                        let dy = (y0 + h) % height * width;
+
+                        // Each row of the sprite is one or two bytes of memory.
+                        let row = self.memory[data + h * row_bytes..data + (h + 1) * row_bytes]
+                            .iter()
+                            .fold(0_u16, |row, &byte| row << 8 | byte as u16);
+
+                        for w in 0..sprite_width {
+                            if self.quirks.clip_sprites && x0 + w >= width {
+                                break;
+                            }
+                            let dx = (x0 + w) % width;
+
+                            if row & (1 << (sprite_width - 1 - w)) != 0 {
+                                // If there was a collision, write to the carry flag.
+                                if self.gfx[dy + dx] & plane != 0 {
+                                    self.v[15] = 1;
+                                }
+                                self.gfx[dy + dx] ^= plane;
+                            }
+                        }
+                    }
+                }
+
+                self.draw_flag = true;
+
+                if self.quirks.display_wait {
+                    return Ok(StepOutcome::WaitingForVBlank);
+                }
+            },
+            // Skips the next instruction if the key stored in Vx is pressed.
+            Instruction::Skp { x } => {
+                if self.key[(self.v[x as usize] & 0xF) as usize] == 1 {
+                    self.skip();
+                }
+            },
+            // Skips the next instruction if the key stored in VX is not pressed.
+            Instruction::Sknp { x } => {
+                if self.key[(self.v[x as usize] & 0xF) as usize] != 1 {
+                    self.skip();
+                }
+            },
+            // Set I to the 16 bit address following the instruction.
+            Instruction::LdILong => {
+                let addr = self.mem_range(self.pc as usize, 2)?.start;
+                self.i = (self.memory[addr] as u16) << 8 | self.memory[addr + 1] as u16;
+                self.pc = self.pc.wrapping_add(2);
+            },
+            // Select the bitplanes to draw to.
+            Instruction::Plane(n) => {
+                self.planes = n & 0x3;
+            },
+            // Load the audio pattern from memory at i.
+            Instruction::Audio => {
+                let src = self.mem_range(self.i as usize, 16)?;
+                self.audio_pattern.copy_from_slice(&self.memory[src]);
+            },
+            // Set VX to the value of the delay timer.
+            Instruction::LdVxDt { x } => {
+                self.v[x as usize] = self.delay_timer;
+            },
+            // A key press is awaited, and then stored in Vx.
+            Instruction::LdVxK { x } => {
+                match self.key.iter().position(|&key| key == 1) {
+                    Some(i) => self.v[x as usize] = i as u8,
+                    None => {
+                        self.pc = self.pc.wrapping_sub(2);
+                        return Ok(StepOutcome::WaitingForKey);
+                    }
+                }
+            },
+            // Set the delay timer to Vx.
+            Instruction::LdDtVx { x } => {
+                self.delay_timer = self.v[x as usize];
+            },
+            // Set the sound timer to Vx.
+            Instruction::LdStVx { x } => {
+                let value = self.v[x as usize];
+                self.set_sound_timer(value);
+            },
+            // Adds Vx to I.
+            Instruction::AddI { x } => {
+                self.i = self.i.wrapping_add(self.v[x as usize] as u16);
+            },
+            // Set I to the sprite for the character in Vx.
+            Instruction::LdF { x } => {
+                self.i = (self.v[x as usize] & 0xF) as u16 * 5;
+            },
+            // Set I to the large sprite for the character in Vx.
+            Instruction::LdHf { x } => {
+                self.i = (BIG_FONTSET_ADDR + (self.v[x as usize] & 0xF) as usize * 10) as u16;
+            },
+            // Set the audio pattern pitch to Vx.
+            Instruction::Pitch { x } => {
+                self.pitch = self.v[x as usize];
+            },
+            // Stores the binary-coded decimal representation of Vx, in i to i+2.
+            Instruction::LdB { x } => {
+                let vx = self.v[x as usize];
+                let bcd = self.mem_range(self.i as usize, 3)?.start;
+                self.last_write = Some(bcd..bcd + 3);
+                self.memory[bcd]     = vx / 100;
+                self.memory[bcd + 1] = (vx / 10)  % 10;
+                self.memory[bcd + 2] = (vx % 100) % 10;
+            },
+            // Stores V0 to Vx in memory starting at address i.
+            Instruction::LdIVx { x } => {
+                let x = x as usize;
+                let dest = self.mem_range(self.i as usize, x + 1)?;
+                self.last_write = Some(dest.clone());
+                self.memory[dest].copy_from_slice(&self.v[0..(x + 1)]);
+                if self.quirks.load_store_increments_i {
+                    self.i = self.i.wrapping_add(x as u16 + 1);
+                }
+            },
+            // Fills V0 to Vx with values from memory starting at address i.
+            Instruction::LdVxI { x } => {
+                let x = x as usize;
+                let src = self.mem_range(self.i as usize, x + 1)?;
+                self.v[0..(x + 1)].copy_from_slice(&self.memory[src]);
+                if self.quirks.load_store_increments_i {
+                    self.i = self.i.wrapping_add(x as u16 + 1);
+                }
+            },
+            // Stores V0 to Vx in the RPL user flags.
+            Instruction::LdRVx { x } => {
+                let x = x as usize;
+                self.rpl[0..(x + 1)].copy_from_slice(&self.v[0..(x + 1)]);
+            },
+            // Fills V0 to Vx from the RPL user flags.
+            Instruction::LdVxR { x } => {
+                let x = x as usize;
+                self.v[0..(x + 1)].copy_from_slice(&self.rpl[0..(x + 1)]);
+            },
+        }
+
+        Ok(StepOutcome::Continue)
+    }
+}
+
+#[test]
+fn test_opcode_0x0000() {
+    // 0000 used to clear the screen like 00E0. It is now unknown, so a
+    // program that runs into empty memory stops with an error.
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.opcode = 0x0000;
+    c8.gfx = vec![255; 2048];
+
+    assert_eq!(c8.execute(), Err(Chip8Error::UnknownOpcode { pc: 0x200, opcode: 0x0000 }));
+    assert!(c8.gfx.iter().all(|&pixel| pixel == 255), "c8.gfx left alone.");
+    assert_eq!(c8.pc, 512);
+}
+
+#[test]
+fn test_opcode_0x00e0() {
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.opcode = 0x00E0;
+    c8.gfx = vec![1; 2048];
+
+    c8.execute().unwrap();
+
+    assert!(c8.gfx.iter().zip([0; 2048].iter()).all(|(a,b)| a == b), "c8.gfx not cleared properly.");
+    assert_eq!(c8.pc, 514);
+}
+
+#[test]
+fn test_opcode_0x00ee() {
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.opcode = 0x00EE;
+    c8.sp = 1;
+    c8.stack[0] = 524;
+
+    c8.execute().unwrap();
+
+    assert_eq!(c8.pc, 524, "Program counter set to new address.");
+    assert_eq!(c8.sp, 0, "Stack pointer decremented.");
+}
+
+#[test]
+fn test_opcode_0x000e() {
+    // 000E used to return like 00EE. It is now unknown too.
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.opcode = 0x000E;
+    c8.sp = 1;
+    c8.stack[0] = 524;
+
+    assert_eq!(c8.execute(), Err(Chip8Error::UnknownOpcode { pc: 0x200, opcode: 0x000E }));
+    assert_eq!((c8.pc, c8.sp), (512, 1), "Nothing returned from.");
+}
+
+#[test]
+fn test_opcode_0x1000() {
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.opcode = 0x1A2A;
+
+    c8.execute().unwrap();
+
+    assert_eq!(c8.pc, 0x0A2A, "Program counter updated.");
+}
+
+#[test]
+fn test_opcode_0x2000() {
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.opcode = 0x2ABC;
+    c8.pc = 0x23;
+
+    c8.execute().unwrap();
+
+    assert_eq!(c8.pc, 0x0ABC, "Program counter updated to new address.");
+    assert_eq!(c8.sp, 1, "Stack poiter incremented.");
+    assert_eq!(c8.stack[0], 0x23 + 2, "Stack holds previous address.");
+}
+
+#[test]
+fn test_opcode_0x3000() {
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.opcode = 0x31AB;
+    c8.v[1] = 0xAB;
+
+    c8.execute().unwrap();
+
+    assert_eq!(c8.pc, 516, "Instruction skipped.");
+
+    c8.opcode = 0x31AA;
+
+    c8.execute().unwrap();
+
+    assert_eq!(c8.pc, 518, "Program counter incremented.");
+}
+
+#[test]
+fn test_opcode_0x4000() {
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.opcode = 0x41AA;
+    c8.v[1] = 0xAB;
+
+    c8.execute().unwrap();
+
+    assert_eq!(c8.pc, 516, "Instruction skipped.");
+
+    c8.opcode = 0x41AB;
+
+    c8.execute().unwrap();
+
+    assert_eq!(c8.pc, 518, "Program counter incremented.");
+}
+
+#[test]
+fn test_opcode_0x5000() {
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.opcode = 0x5AB0;
+    c8.v[0xA] = 1;
+    c8.v[0xB] = 1;
+
+    c8.execute().unwrap();
+
+    assert_eq!(c8.pc, 516, "Instruction skipped.");
+
+    c8.v[0xB] = 0;
+
+    c8.execute().unwrap();
+
+    assert_eq!(c8.pc, 518, "Program counter incremented.");
+}
+
+#[test]
+fn test_opcode_0x7000(){
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.opcode = 0x71FF;
+    c8.v[1] = 0xFF;
+
+    c8.execute().unwrap();
+    assert_eq!{c8.v[1], 0xFE};
+
+    c8.opcode = 0x71FF;
+    c8.v[1] = 0;
+
+    c8.execute().unwrap();
+    assert_eq!{c8.v[1], 0xFF};
+
+    c8.opcode = 0x7100;
+    c8.v[1] = 0;
+
+    c8.execute().unwrap();
+    assert_eq!{c8.v[1], 0};
+
+    c8.opcode = 0x71AB;
+    c8.v[1] = 0x11;
+
+    c8.execute().unwrap();
+    assert_eq!{c8.v[1], 0xBC};
+}
+
+#[test]
+fn test_opcode_0x8004(){
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.opcode  = 0x8124;
+    c8.v[1]    = 0xFF;
+    c8.v[2]    = 0xFF;
+
+    c8.execute().unwrap();
+    assert_eq!{c8.v[1], 0xFE};
+    assert_eq!{c8.v[15], 0x1};
+
+    c8.opcode = 0x8124;
+    c8.v[1]   = 0x0;
+    c8.v[2]   = 0x0;
+    c8.v[15]  = 0x1;
+
+    c8.execute().unwrap();
+    assert_eq!{c8.v[1],  0x0};
+    assert_eq!{c8.v[15], 0x0};
+
+    c8.opcode = 0x8124;
+    c8.v[1]   = 0x0;
+    c8.v[2]   = 0xFF;
+    c8.v[15]  = 0x1;
+
+    c8.execute().unwrap();
+    assert_eq!{c8.v[1],  0xFF};
+    assert_eq!{c8.v[15],  0x0};
+
+    c8.opcode = 0x8124;
+    c8.v[1] = 0x11;
+    c8.v[2] = 0x12;
+
+    c8.execute().unwrap();
+    assert_eq!{c8.v[1], 0x23};
+    assert_eq!{c8.v[15], 0x0};
+}
+
+#[test]
+fn test_opcode_0x8005(){
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.opcode  = 0x8125;
+    c8.v[1]    = 0xFF;
+    c8.v[2]    = 0xFF;
+
+    c8.execute().unwrap();
+    assert_eq!{c8.v[1], 0x00};
+    assert_eq!{c8.v[15], 0x1};
+
+    c8.opcode = 0x8125;
+    c8.v[1]   = 0x0;
+    c8.v[2]   = 0x0;
+    c8.v[15]  = 0x0;
+
+    c8.execute().unwrap();
+    assert_eq!{c8.v[1],  0x0};
+    assert_eq!{c8.v[15], 0x1};
+
+    c8.opcode = 0x8125;
+    c8.v[1]   = 0x0;
+    c8.v[2]   = 0xFF;
+    c8.v[15]  = 0x0;
+
+    c8.execute().unwrap();
+    assert_eq!{c8.v[1],  0x01};
+    assert_eq!{c8.v[15],  0x0};
+
+    c8.opcode = 0x8125;
+    c8.v[1]   = 0x11;
+    c8.v[2]   = 0x12;
+    c8.v[15]  = 0x1;
+
+    c8.execute().unwrap();
+    assert_eq!{c8.v[1], 0xFF};
+    assert_eq!{c8.v[15], 0x0};
+}
+
+#[test]
+fn test_opcode_0x8006() {
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.opcode = 0x8106;
+    c8.v[1] = 0b00110000;
+    c8.v[15] = 1;
+
+    c8.execute().unwrap();
+    assert_eq!(c8.v[1], 0b00011000, "Vx shifted 1 bit right.");
+    assert_eq!(c8.v[15], 0, "Carry flag set to 0.");
+
+    c8.v[1] = 0b00110001;
+    c8.v[15] = 0;
+    c8.execute().unwrap();
+    assert_eq!(c8.v[1], 0b00011000, "Vx shifted 1 bit right.");
+    assert_eq!(c8.v[15], 1, "Carry flag set to 1.");
+}
+
+#[test]
+fn test_opcode_0x8007() {
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.opcode  = 0x8127;
+    c8.v[1]    = 0xFF;
+    c8.v[2]    = 0xFF;
+
+    c8.execute().unwrap();
+    assert_eq!{c8.v[1], 0x00};
+    assert_eq!{c8.v[15], 0x1};
+
+    c8.opcode = 0x8127;
+    c8.v[1]   = 0x0;
+    c8.v[2]   = 0x0;
+    c8.v[15]  = 0x1;
+
+    c8.execute().unwrap();
+    assert_eq!{c8.v[1],  0x0};
+    assert_eq!{c8.v[15], 0x1};
+
+    c8.opcode = 0x8127;
+    c8.v[1]   = 0xFF;
+    c8.v[2]   = 0x00;
+    c8.v[15]  = 0x1;
+
+    c8.execute().unwrap();
+    assert_eq!{c8.v[1],  0x01};
+    assert_eq!{c8.v[15],  0x0};
+
+    c8.opcode = 0x8127;
+    c8.v[1] = 0x12;
+    c8.v[2] = 0x11;
+
+    c8.execute().unwrap();
+    assert_eq!{c8.v[1], 0xFF};
+    assert_eq!{c8.v[15], 0x0};
+}
+
+#[test]
+fn test_opcode_0x800e() {
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.opcode = 0x810E;
+    c8.v[1] = 0b10110000;
+
+    c8.execute().unwrap();
+    assert_eq!(c8.v[1], 0b01100000, "Vx shifted 1 bit left.");
+    assert_eq!(c8.v[15], 1, "Carry flag set to 1.");
+
+    c8.v[1] = 0b00110001;
+    c8.v[15] = 1;
+    c8.execute().unwrap();
+    assert_eq!(c8.v[1], 0b01100010, "Vx shifted 1 bit left.");
+    assert_eq!(c8.v[15], 0, "Carry flag set to 0.");
+}
+
+#[test]
+fn test_unknown_opcode() {
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.opcode = 0xE0FF;
+
+    assert_eq!(c8.execute(), Err(Chip8Error::UnknownOpcode { pc: 0x200, opcode: 0xE0FF }));
+    assert_eq!(c8.pc, 0x200, "Program counter left at the faulting instruction.");
+}
+
+#[test]
+fn test_stack_faults() {
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.opcode = 0x00EE;
+
+    assert_eq!(c8.execute(), Err(Chip8Error::StackUnderflow));
+
+    c8.opcode = 0x2200;
+    for _ in 0..16 {
+        c8.execute().unwrap();
+    }
+    assert_eq!(c8.execute(), Err(Chip8Error::StackOverflow));
+    assert_eq!(c8.sp, 16);
+}
+
+#[test]
+fn test_memory_out_of_bounds() {
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.opcode = 0xF255;
+    c8.i = 0xFFE;
+
+    assert_eq!(c8.execute(), Err(Chip8Error::MemoryOutOfBounds { addr: 0x1000 }));
+
+    c8.opcode = 0xD015;
+    c8.i = 0xFFF;
+    assert_eq!(c8.execute(), Err(Chip8Error::MemoryOutOfBounds { addr: 0x1000 }));
+
+    c8.pc = 0xFFF;
+    assert_eq!(c8.fetch(), Err(Chip8Error::MemoryOutOfBounds { addr: 0x1000 }));
+}
+
+#[test]
+fn test_opcode_0xf029() {
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.opcode = 0xF129;
+    c8.v[1] = 0xFF;
+
+    c8.execute().unwrap();
+    assert_eq!(c8.i, 0xF * 5, "Only the low nibble of Vx selects a character.");
+}
+
+#[test]
+fn test_quirk_shift_uses_vy() {
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.quirks = Quirks::default();
+    c8.quirks.shift_uses_vy = true;
+    c8.opcode = 0x8126;
+    c8.v[1] = 0;
+    c8.v[2] = 0b00000011;
+
+    c8.execute().unwrap();
+    assert_eq!(c8.v[1], 0b00000001, "Vy shifted into Vx.");
+    assert_eq!(c8.v[15], 1);
+
+    c8.opcode = 0x812E;
+    c8.v[2] = 0b10000001;
+    c8.execute().unwrap();
+    assert_eq!(c8.v[1], 0b00000010, "Vy shifted into Vx.");
+    assert_eq!(c8.v[15], 1);
+}
+
+#[test]
+fn test_quirk_load_store_increments_i() {
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.quirks = Quirks::default();
+    c8.opcode = 0xF255;
+    c8.i = 0x300;
+
+    c8.execute().unwrap();
+    assert_eq!(c8.i, 0x300, "I unchanged by default.");
+
+    c8.quirks.load_store_increments_i = true;
+    c8.execute().unwrap();
+    assert_eq!(c8.i, 0x303);
+
+    c8.opcode = 0xF065;
+    c8.execute().unwrap();
+    assert_eq!(c8.i, 0x304);
+}
+
+#[test]
+fn test_quirk_vf_reset() {
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.quirks = Quirks::default();
+    c8.opcode = 0x8121;
+    c8.v[15] = 1;
+
+    c8.execute().unwrap();
+    assert_eq!(c8.v[15], 1, "Vf untouched by default.");
+
+    c8.quirks.vf_reset = true;
+    c8.execute().unwrap();
+    assert_eq!(c8.v[15], 0);
+}
+
+#[test]
+fn test_quirk_jump_uses_vx() {
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.quirks = Quirks::default();
+    c8.opcode = 0xB210;
+    c8.v[0] = 0x1;
+    c8.v[2] = 0x2;
+
+    c8.execute().unwrap();
+    assert_eq!(c8.pc, 0x211);
+
+    c8.quirks.jump_uses_vx = true;
+    c8.execute().unwrap();
+    assert_eq!(c8.pc, 0x212);
+}
+
+#[test]
+fn test_quirk_clip_sprites() {
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.quirks = Quirks::default();
+    // Draw a solid 8x2 sprite at the bottom right corner.
+    c8.opcode = 0xD012;
+    c8.i = 0x300;
+    c8.memory[0x300] = 0xFF;
+    c8.memory[0x301] = 0xFF;
+    c8.v[0] = 60;
+    c8.v[1] = 31;
+
+    c8.execute().unwrap();
+    assert_eq!(c8.gfx[31 * 64 + 60], 1);
+    assert_eq!(c8.gfx[31 * 64 + 3], 1, "Row wraps horizontally.");
+    assert_eq!(c8.gfx[60], 1, "Sprite wraps vertically.");
+
+    c8.gfx = vec![0; 2048];
+    c8.quirks.clip_sprites = true;
+    c8.execute().unwrap();
+    assert_eq!(c8.gfx[31 * 64 + 60], 1);
+    assert_eq!(c8.gfx.iter().filter(|&&p| p != 0).count(), 4, "Sprite clipped at the edges.");
+}
+
+#[test]
+fn test_quirk_display_wait() {
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.quirks = Quirks::default();
+    c8.opcode = 0xD011;
+
+    assert_eq!(c8.execute(), Ok(StepOutcome::Continue));
+
+    c8.quirks.display_wait = true;
+    assert_eq!(c8.execute(), Ok(StepOutcome::WaitingForVBlank));
+}
+
+#[test]
+fn test_new_uses_platform() {
+    let c8 = Chip8::new(Platform::CosmacVip);
+    assert_eq!(c8.memory.len(), 4096);
+    assert_eq!(c8.quirks, Quirks::cosmac_vip());
+
+    let c8 = Chip8::new(Platform::XoChip);
+    assert_eq!(c8.memory.len(), 0x10000);
+    assert_eq!(&c8.memory[..CHIP8_FONTSET.len()], &CHIP8_FONTSET[..]);
+}
+
+#[test]
+fn test_schip_requires_platform() {
+    let mut c8 = Chip8::new(Platform::CosmacVip);
+    c8.opcode = 0x00FF;
+    assert_eq!(c8.execute(), Err(Chip8Error::UnknownOpcode { pc: 0x200, opcode: 0x00FF }));
+
+    let mut c8 = Chip8::new(Platform::SuperChip10);
+    c8.opcode = 0x00C1;
+    assert_eq!(c8.execute(), Err(Chip8Error::UnknownOpcode { pc: 0x200, opcode: 0x00C1 }));
+}
+
+#[test]
+fn test_opcode_0x00ff() {
+    let mut c8 = Chip8::new(Platform::SuperChip11);
+    c8.opcode = 0x00FF;
+
+    c8.execute().unwrap();
+    assert_eq!(c8.display_size(), (128, 64));
+    assert_eq!(c8.gfx.len(), 128 * 64);
+
+    c8.opcode = 0x00FE;
+    c8.execute().unwrap();
+    assert_eq!(c8.display_size(), (64, 32));
+    assert_eq!(c8.gfx.len(), 64 * 32);
+}
+
+#[test]
+fn test_opcode_0x00cn() {
+    let mut c8 = Chip8::new(Platform::SuperChip11);
+    c8.gfx[0] = 1;
+    c8.gfx[64 * 31] = 1;
+    c8.opcode = 0x00C2;
+
+    c8.execute().unwrap();
+    assert_eq!(c8.gfx[64 * 2], 1, "Pixel moved down 2 rows.");
+    assert_eq!(c8.gfx.iter().filter(|&&p| p != 0).count(), 1, "Bottom row scrolled off.");
+}
+
+#[test]
+fn test_opcode_0x00dn() {
+    let mut c8 = Chip8::new(Platform::XoChip);
+    c8.gfx[0] = 1;
+    c8.gfx[64 * 31] = 1;
+    c8.opcode = 0x00D2;
+
+    c8.execute().unwrap();
+    assert_eq!(c8.gfx[64 * 29], 1, "Pixel moved up 2 rows.");
+    assert_eq!(c8.gfx.iter().filter(|&&p| p != 0).count(), 1, "Top row scrolled off.");
+
+    c8.platform = Platform::SuperChip11;
+    assert!(c8.execute().is_err(), "XO-CHIP only.");
+}
+
+#[test]
+fn test_opcode_0x00fb_0x00fc() {
+    let mut c8 = Chip8::new(Platform::SuperChip11);
+    c8.gfx[62] = 1;
+    c8.gfx[64 + 2] = 1;
+    c8.opcode = 0x00FB;
+
+    c8.execute().unwrap();
+    assert_eq!(c8.gfx[64 + 6], 1, "Pixel moved right 4.");
+    assert_eq!(c8.gfx.iter().filter(|&&p| p != 0).count(), 1, "Pixel scrolled off the right edge.");
+
+    c8.opcode = 0x00FC;
+    c8.execute().unwrap();
+    assert_eq!(c8.gfx[64 + 2], 1, "Pixel moved left 4.");
+}
+
+#[test]
+fn test_opcode_0x00fd() {
+    let mut c8 = Chip8::new(Platform::SuperChip11);
+    c8.opcode = 0x00FD;
+
+    assert_eq!(c8.execute(), Ok(StepOutcome::Exit));
+    assert_eq!(c8.pc, 0x200, "Program stays on the exit instruction.");
+}
+
+#[test]
+fn test_opcode_0xdxy0() {
+    let mut c8 = Chip8::new(Platform::SuperChip11);
+    c8.hires = true;
+    c8.gfx = vec![0; 128 * 64];
+    c8.i = 0x300;
+    for byte in c8.memory[0x300..0x320].iter_mut() {
+        *byte = 0xFF;
+    }
+    c8.opcode = 0xD010;
+
+    c8.execute().unwrap();
+    assert_eq!(c8.gfx.iter().filter(|&&p| p != 0).count(), 256, "16x16 sprite drawn.");
+    assert_eq!(c8.gfx[15 * 128 + 15], 1);
+    assert_eq!(c8.v[15], 0);
+}
+
+#[test]
+fn test_opcode_0xfx30() {
+    let mut c8 = Chip8::new(Platform::SuperChip11);
+    c8.opcode = 0xF130;
+    c8.v[1] = 3;
+
+    c8.execute().unwrap();
+    assert_eq!(&c8.memory[c8.i as usize..c8.i as usize + 10], &BIG_FONTSET[30..40]);
+}
+
+#[test]
+fn test_opcode_0xfx75_0xfx85() {
+    let mut c8 = Chip8::new(Platform::SuperChip11);
+    c8.v[0..3].copy_from_slice(&[1, 2, 3]);
+    c8.opcode = 0xF275;
+
+    c8.execute().unwrap();
+    assert_eq!(&c8.rpl[0..4], &[1, 2, 3, 0]);
+
+    c8.v = [0; 16];
+    c8.opcode = 0xF185;
+    c8.execute().unwrap();
+    assert_eq!(&c8.v[0..3], &[1, 2, 0]);
+}
+
+#[test]
+fn test_opcode_0xf000() {
+    let mut c8 = Chip8::new(Platform::XoChip);
+    c8.memory[0x202] = 0xAB;
+    c8.memory[0x203] = 0xCD;
+    c8.opcode = 0xF000;
+
+    c8.execute().unwrap();
+    assert_eq!(c8.i, 0xABCD);
+    assert_eq!(c8.pc, 0x204, "Address word skipped.");
+}
+
+#[test]
+fn test_top_of_memory() {
+    let mut c8 = Chip8::new(Platform::XoChip);
+    c8.pc = 0xFFFE;
+    c8.memory[0xFFFE] = 0x00;
+    c8.memory[0xFFFF] = 0xE0;
+    c8.step().unwrap();
+    assert_eq!(c8.pc, 0x0000, "Program counter wrapped.");
+
+    // A skip at 0xFFFC over the instruction at 0xFFFE.
+    c8.pc = 0xFFFC;
+    c8.memory[0xFFFC] = 0x30;
+    c8.memory[0xFFFD] = 0x00;
+    c8.step().unwrap();
+    assert_eq!(c8.pc, 0x0000, "Skip wrapped.");
+
+    // A long load whose address word is the last in memory.
+    c8.pc = 0xFFFC;
+    c8.memory[0xFFFC] = 0xF0;
+    c8.memory[0xFFFD] = 0x00;
+    c8.memory[0xFFFE] = 0x12;
+    c8.memory[0xFFFF] = 0x34;
+    c8.step().unwrap();
+    assert_eq!((c8.i, c8.pc), (0x1234, 0x0000));
+
+    c8.quirks.load_store_increments_i = true;
+    c8.i = 0xFFFF;
+    c8.opcode = 0xF055;
+    c8.execute().unwrap();
+    assert_eq!(c8.i, 0x0000, "I wrapped after storing.");
+    c8.i = 0xFFFF;
+    c8.opcode = 0xF065;
+    c8.execute().unwrap();
+    assert_eq!(c8.i, 0x0000, "I wrapped after loading.");
+
+    // Exiting at address 0 leaves the program counter where it was.
+    c8.pc = 0x0000;
+    c8.memory[0x0000] = 0x00;
+    c8.memory[0x0001] = 0xFD;
+    assert_eq!(c8.step().unwrap(), StepOutcome::Exit);
+    assert_eq!(c8.pc, 0x0000);
+}
+
+#[test]
+fn test_skip_over_long_load() {
+    let mut c8 = Chip8::new(Platform::XoChip);
+    c8.memory[0x202] = 0xF0;
+    c8.memory[0x203] = 0x00;
+    c8.opcode = 0x3000;
+
+    c8.execute().unwrap();
+    assert_eq!(c8.pc, 0x206, "Skipped all four bytes of F000 NNNN.");
+}
+
+#[test]
+fn test_opcode_0x5xy2_0x5xy3() {
+    let mut c8 = Chip8::new(Platform::XoChip);
+    c8.v[1..4].copy_from_slice(&[1, 2, 3]);
+    c8.i = 0x300;
+    c8.opcode = 0x5132;
+
+    c8.execute().unwrap();
+    assert_eq!(&c8.memory[0x300..0x303], &[1, 2, 3]);
+    assert_eq!(c8.i, 0x300, "I unchanged.");
+
+    c8.opcode = 0x5643;
+    c8.execute().unwrap();
+    assert_eq!(&c8.v[4..7], &[3, 2, 1], "Registers loaded in reverse.");
+}
+
+#[test]
+fn test_opcode_0xfn01() {
+    let mut c8 = Chip8::new(Platform::XoChip);
+    c8.i = 0x300;
+    c8.memory[0x300] = 0x80; // Plane one: leftmost pixel.
+    c8.memory[0x301] = 0xC0; // Plane two: leftmost two pixels.
+    c8.opcode = 0xF301;
+    c8.execute().unwrap();
+
+    c8.opcode = 0xD001;
+    c8.execute().unwrap();
+    assert_eq!(&c8.gfx[0..3], &[3, 2, 0]);
+
+    c8.opcode = 0xF201;
+    c8.execute().unwrap();
+    c8.opcode = 0x00E0;
+    c8.execute().unwrap();
+    assert_eq!(&c8.gfx[0..3], &[1, 0, 0], "Only plane two cleared.");
+}
+
+#[test]
+fn test_opcode_0xf002_0xfx3a() {
+    let mut c8 = Chip8::new(Platform::XoChip);
+    for (n, byte) in c8.memory[0x300..0x310].iter_mut().enumerate() {
+        *byte = n as u8;
+    }
+    c8.i = 0x300;
+    c8.opcode = 0xF002;
+
+    c8.execute().unwrap();
+    assert_eq!(c8.audio_pattern[15], 15);
+
+    c8.v[2] = 112;
+    c8.opcode = 0xF23A;
+    c8.execute().unwrap();
+    assert_eq!(c8.pitch, 112);
+    assert_eq!(c8.audio_pattern().unwrap().rate(), 8000.0);
+
+    c8.platform = Platform::SuperChip11;
+    assert_eq!(c8.audio_pattern(), None, "Only XO-CHIP plays patterns.");
+}
+
+#[test]
+fn test_load_large_game() {
+    let path = std::env::temp_dir().join("chip8_test_load_large_game.ch8");
+    std::fs::write(&path, vec![0xAA; 0x8000]).unwrap();
+
+    let mut c8 = Chip8::new(Platform::XoChip);
+    c8.load_game(path.to_str().unwrap()).unwrap();
+    assert_eq!(c8.memory[0x200 + 0x7FFF], 0xAA);
+
+    let mut c8 = Chip8::new(Platform::CosmacVip);
+    c8.load_game(path.to_str().unwrap()).unwrap();
+    assert_eq!(c8.memory[0xFFF], 0xAA, "Truncated to fit memory.");
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_sound_events() {
+    let mut c8 = Chip8::new(Platform::CosmacVip);
+    c8.v[0] = 2;
+    c8.opcode = 0xF018;
+
+    c8.execute().unwrap();
+    assert_eq!(c8.take_sound_events(), vec![SoundEvent::Start]);
+
+    c8.tick_timers();
+    assert_eq!(c8.take_sound_events(), vec![], "Still sounding.");
+
+    c8.tick_timers();
+    assert_eq!(c8.take_sound_events(), vec![SoundEvent::Stop]);
+
+    c8.tick_timers();
+    assert_eq!(c8.take_sound_events(), vec![], "Already silent.");
+}
+
+#[test]
+fn test_opcode_0xcxnn_seeded() {
+    let mut a = Chip8::new(Platform::CosmacVip);
+    let mut b = Chip8::new(Platform::CosmacVip);
+    a.seed_rng(42);
+    b.seed_rng(42);
+
+    for _ in 0..8 {
+        a.opcode = 0xC00F;
+        b.opcode = 0xC00F;
+        a.execute().unwrap();
+        b.execute().unwrap();
+        assert_eq!(a.v[0], b.v[0], "Same seed, same numbers.");
+        assert_eq!(a.v[0] & 0xF0, 0, "Masked by NN.");
+    }
+}
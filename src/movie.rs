@@ -0,0 +1,301 @@
+//! Movies: recordings of a run's input that replay it exactly.
+//!
+//! A movie is a text file. The header names everything that decides how
+//! the run goes besides input: platform, quirks, clock rate, the seed for
+//! CXNN and a hash of the ROM. Then come the frames on which the held keys
+//! changed, and finally the run's length and a hash of `gfx` at its end
+//! so a replay can check it arrived at the same screen.
+//!
+//! ```text
+//! chip8-movie 1
+//! platform vip
+//! quirks shift-uses-vy,load-store-increments-i,vf-reset,clip-sprites,display-wait
+//! ips 660
+//! rng xorshift
+//! seed 000000000000002a
+//! rom 84b0a9c4d8d3a1f2
+//! keys 0 0000
+//! keys 31 0020
+//! end 600 5c6ec1f4d2a0e9b7
+//! ```
+//!
+//! Keys are a bitmask with bit N set while key N is held.
+
+use std::io;
+use std::io::prelude::*;
+
+use hash::fnv1a;
+use machine::Chip8;
+use platform::Platform;
+use quirks::Quirks;
+use random::Generator;
+
+/// The current format version.
+pub const MOVIE_VERSION: u32 = 1;
+
+/// A recording of a run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    pub platform: Platform,
+    pub quirks:   Quirks,
+    pub ips:      u32,
+    pub rng:      Generator,
+    pub seed:     u64,
+    pub rom_hash: u64,
+    pub keys:     Vec<(u32, u16)>,  // Frames on which the held keys changed, in order.
+    pub frames:   u32,              // Length of the run.
+    pub gfx_hash: u64               // Hash of gfx after the last frame.
+}
+
+fn key_mask(key: &[u8; 16]) -> u16 {
+    key.iter().enumerate().fold(0, |mask, (n, &held)| mask | ((held != 0) as u16) << n)
+}
+
+fn invalid(line: usize, message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid movie, line {}: {}", line, message))
+}
+
+impl Movie {
+    /// Starts a recording of `c8`, which should have just loaded `rom`.
+    /// Reseeds its random number generator with `seed`.
+    pub fn record(c8: &mut Chip8, rom: &[u8], ips: u32, seed: u64) -> Movie {
+        c8.seed_rng(seed);
+        Movie {
+            platform: c8.platform,
+            quirks:   c8.quirks,
+            ips,
+            rng:      c8.rng.generator(),
+            seed,
+            rom_hash: fnv1a(rom),
+            keys:     Vec::new(),
+            frames:   0,
+            gfx_hash: 0
+        }
+    }
+
+    /// Replaces `c8` with a new machine for the movie's platform and
+    /// quirks, with `rom` loaded, ready to replay the movie.
+    pub fn start_replay(&self, c8: &mut Chip8, rom: &[u8]) -> Result<(), String> {
+        if fnv1a(rom) != self.rom_hash {
+            return Err("the movie was recorded with a different ROM".to_string());
+        }
+        let old = std::mem::replace(c8, Chip8::new(self.platform));
+        c8.quirks = self.quirks;
+        // Keep the generator, and any VIP interpreter it reads from, if it
+        // is the kind the movie was recorded with.
+        c8.rng = old.rng;
+        if c8.rng.generator() == self.rng {
+            c8.rng.reseed(self.seed);
+        } else {
+            c8.rng = self.rng.create(self.seed);
+        }
+        c8.load_rom(rom);
+        Ok(())
+    }
+
+    /// Records the keys held during `frame`, which must come after any
+    /// frame recorded before.
+    pub fn record_frame(&mut self, frame: u32, key: &[u8; 16]) {
+        let mask = key_mask(key);
+        let last = self.keys.last().map_or(0, |&(_, mask)| mask);
+        if mask != last || self.keys.is_empty() {
+            self.keys.push((frame, mask));
+        }
+        self.frames = frame + 1;
+    }
+
+    /// Records the screen at the end of the run.
+    pub fn finish(&mut self, c8: &Chip8) {
+        self.gfx_hash = fnv1a(&c8.gfx);
+    }
+
+    /// The keys held during `frame`.
+    pub fn keys_at(&self, frame: u32) -> [u8; 16] {
+        let mask = match self.keys.binary_search_by_key(&frame, |&(frame, _)| frame) {
+            Ok(n) => self.keys[n].1,
+            Err(0) => 0,
+            Err(n) => self.keys[n - 1].1
+        };
+        let mut key = [0; 16];
+        for (n, held) in key.iter_mut().enumerate() {
+            *held = (mask >> n & 1) as u8;
+        }
+        key
+    }
+
+    /// Whether `c8`, having replayed every frame, ended on the recorded screen.
+    pub fn verify(&self, c8: &Chip8) -> bool {
+        fnv1a(&c8.gfx) == self.gfx_hash
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "chip8-movie {}", MOVIE_VERSION)?;
+        writeln!(writer, "platform {}", self.platform.name())?;
+        writeln!(writer, "quirks {}", self.quirks)?;
+        writeln!(writer, "ips {}", self.ips)?;
+        writeln!(writer, "rng {}", self.rng.name())?;
+        writeln!(writer, "seed {:016x}", self.seed)?;
+        writeln!(writer, "rom {:016x}", self.rom_hash)?;
+        for &(frame, mask) in self.keys.iter() {
+            writeln!(writer, "keys {} {:04x}", frame, mask)?;
+        }
+        writeln!(writer, "end {} {:016x}", self.frames, self.gfx_hash)
+    }
+
+    pub fn read<R: BufRead>(reader: R) -> io::Result<Movie> {
+        let mut movie = Movie {
+            platform: Platform::default(),
+            quirks:   Quirks::default(),
+            ips:      0,
+            rng:      Generator::default(),
+            seed:     0,
+            rom_hash: 0,
+            keys:     Vec::new(),
+            frames:   0,
+            gfx_hash: 0
+        };
+        let mut ended = false;
+
+        for (n, line) in reader.lines().enumerate() {
+            let line = line?;
+            let n = n + 1;
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let hex = |field: usize| {
+                fields.get(field).and_then(|value| u64::from_str_radix(value, 16).ok())
+                    .ok_or_else(|| invalid(n, "expected a hexadecimal number"))
+            };
+            let dec = |field: usize| {
+                fields.get(field).and_then(|value| value.parse::<u32>().ok())
+                    .ok_or_else(|| invalid(n, "expected a number"))
+            };
+
+            if n == 1 {
+                if fields.first() != Some(&"chip8-movie") {
+                    return Err(invalid(n, "not a movie"));
+                }
+                if dec(1)? != MOVIE_VERSION {
+                    return Err(invalid(n, "unsupported version"));
+                }
+                continue;
+            }
+            if ended {
+                return Err(invalid(n, "data after end"));
+            }
+
+            match fields.first() {
+                Some(&"platform") => {
+                    movie.platform = fields.get(1).unwrap_or(&"").parse().map_err(|err: String| invalid(n, &err))?;
+                },
+                Some(&"quirks") => {
+                    movie.quirks = fields.get(1).unwrap_or(&"").parse().map_err(|err: String| invalid(n, &err))?;
+                },
+                Some(&"ips") => movie.ips = dec(1)?,
+                Some(&"rng") => {
+                    movie.rng = fields.get(1).unwrap_or(&"").parse().map_err(|err: String| invalid(n, &err))?;
+                },
+                Some(&"seed") => movie.seed = hex(1)?,
+                Some(&"rom") => movie.rom_hash = hex(1)?,
+                Some(&"keys") => {
+                    let frame = dec(1)?;
+                    if movie.keys.last().is_some_and(|&(last, _)| frame <= last) {
+                        return Err(invalid(n, "frames out of order"));
+                    }
+                    movie.keys.push((frame, hex(2)? as u16));
+                },
+                Some(&"end") => {
+                    movie.frames = dec(1)?;
+                    movie.gfx_hash = hex(2)?;
+                    ended = true;
+                },
+                None => continue,
+                Some(_) => return Err(invalid(n, "unknown line"))
+            }
+        }
+
+        if !ended {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "invalid movie: no end line"));
+        }
+        if movie.ips == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid movie: no ips line"));
+        }
+        Ok(movie)
+    }
+}
+
+#[test]
+fn test_movie_round_trip() {
+    let rom = [0x60, 0x01, 0x12, 0x02];
+    let mut c8 = Chip8::new(Platform::XoChip);
+    c8.rng = Generator::Vip.create(0);
+    let mut movie = Movie::record(&mut c8, &rom, 1800, 42);
+
+    let mut key = [0; 16];
+    movie.record_frame(0, &key);
+    movie.record_frame(1, &key);
+    key[5] = 1;
+    movie.record_frame(2, &key);
+    movie.finish(&c8);
+    assert_eq!(movie.keys, vec![(0, 0), (2, 0x20)]);
+    assert_eq!(movie.frames, 3);
+
+    let mut text = Vec::new();
+    movie.write(&mut text).unwrap();
+    let read = Movie::read(&text[..]).unwrap();
+    assert_eq!(read, movie);
+
+    assert_eq!(read.keys_at(1)[5], 0);
+    assert_eq!(read.keys_at(2)[5], 1);
+    assert_eq!(read.keys_at(100)[5], 1);
+
+    let mut replay = Chip8::new(Platform::CosmacVip);
+    assert!(read.start_replay(&mut replay, &[0x00]).is_err(), "Different ROM.");
+    read.start_replay(&mut replay, &rom).unwrap();
+    // The machine is rebuilt for the movie's platform, memory and all.
+    assert_eq!(replay.platform, Platform::XoChip);
+    assert_eq!(replay.memory.len(), 0x10000);
+    assert_eq!(&replay.memory[0x200..0x204], &rom);
+    assert_eq!(replay.rng.generator(), Generator::Vip);
+    assert!(read.verify(&replay));
+}
+
+#[test]
+fn test_movie_rejects_bad_input() {
+    assert!(Movie::read(&b"not a movie\n"[..]).is_err());
+    assert!(Movie::read(&b"chip8-movie 2\n"[..]).is_err());
+    assert!(Movie::read(&b"chip8-movie 1\nips 660\n"[..]).is_err(), "No end.");
+    assert!(Movie::read(&b"chip8-movie 1\nips 660\nkeys 5 0\nkeys 4 1\nend 6 0\n"[..]).is_err());
+}
+
+#[test]
+fn test_movie_replays_exactly() {
+    use scheduler::Scheduler;
+
+    // Draws a random digit at a random position every frame until key 1 is held.
+    let rom = [0x63, 0x01, 0x00, 0xE0, 0xC0, 0x0F, 0xF0, 0x29, 0xC1, 0x3F, 0xC2, 0x1F,
+               0xD1, 0x25, 0xE3, 0x9E, 0x12, 0x02, 0x12, 0x12];
+    let run = |movie: &mut Movie, c8: &mut Chip8, replay: bool| {
+        let mut scheduler = Scheduler::new(movie.ips);
+        for frame in 0..120 {
+            if replay {
+                c8.key = movie.keys_at(frame);
+            } else {
+                c8.key[1] = (frame >= 90) as u8;
+                movie.record_frame(frame, &c8.key);
+            }
+            scheduler.run_frame(c8).unwrap();
+        }
+    };
+
+    let mut c8 = Chip8::new(Platform::SuperChip11);
+    c8.memory[0x200..0x200 + rom.len()].copy_from_slice(&rom);
+    let mut movie = Movie::record(&mut c8, &rom, 600, 7);
+    run(&mut movie, &mut c8, false);
+    movie.finish(&c8);
+
+    let mut replay = Chip8::new(Platform::SuperChip11);
+    replay.memory[0x200..0x200 + rom.len()].copy_from_slice(&rom);
+    movie.start_replay(&mut replay, &rom).unwrap();
+    run(&mut movie.clone(), &mut replay, true);
+    assert!(movie.verify(&replay));
+    assert_eq!(replay.pc, 0x212, "Stopped on key 1.");
+}
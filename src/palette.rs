@@ -0,0 +1,87 @@
+use std::str::FromStr;
+
+/// An RGB colour.
+pub type Colour = (u8, u8, u8);
+
+/// The colours a frontend should use to present `Chip8::gfx`.
+///
+/// Each pixel in `gfx` holds a bitmask of the XO-CHIP bitplanes lit at
+/// that position, so `colours[pixel]` is its colour: 0 is the background,
+/// 1 plane one, 2 plane two and 3 both planes. Programs that never select
+/// plane two only ever use the first two colours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub colours: [Colour; 4]
+}
+
+impl Palette {
+    /// Amber on black, like a monochrome monitor of the VIP's day.
+    pub const AMBER: Palette = Palette {
+        colours: [(0x1A, 0x0E, 0x00), (0xFF, 0xB0, 0x00), (0xC0, 0x60, 0x00), (0x70, 0x40, 0x00)]
+    };
+
+    /// Green phosphor on black.
+    pub const GREEN: Palette = Palette {
+        colours: [(0x00, 0x14, 0x00), (0x33, 0xFF, 0x33), (0x10, 0xA0, 0x30), (0x0C, 0x50, 0x18)]
+    };
+
+    /// Dark pixels on a pale green liquid crystal display.
+    pub const LCD: Palette = Palette {
+        colours: [(0x9B, 0xBC, 0x0F), (0x0F, 0x38, 0x0F), (0x30, 0x62, 0x30), (0x8B, 0xAC, 0x0F)]
+    };
+
+    pub fn colour(&self, pixel: u8) -> Colour {
+        self.colours[(pixel & 0x3) as usize]
+    }
+}
+
+/// Parses a palette's name, `default`, `amber`, `green` or `lcd`, or up
+/// to four colours as comma separated hex, background first, such as
+/// `000000,ffffff`. Colours not given are kept from the default.
+impl FromStr for Palette {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Palette, String> {
+        match s {
+            "default" => return Ok(Palette::default()),
+            "amber"   => return Ok(Palette::AMBER),
+            "green"   => return Ok(Palette::GREEN),
+            "lcd"     => return Ok(Palette::LCD),
+            _         => {}
+        }
+
+        let mut palette = Palette::default();
+        let colours: Vec<&str> = s.split(',').collect();
+        if colours.len() > 4 {
+            return Err(format!("Too many colours in palette: {}", s));
+        }
+        for (colour, hex) in palette.colours.iter_mut().zip(colours) {
+            let rgb = match u32::from_str_radix(hex, 16) {
+                Ok(rgb) if hex.len() == 6 => rgb,
+                _ => return Err(format!("Unknown palette: {} (expected default, amber, green, lcd or hex colours)", s))
+            };
+            *colour = ((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8);
+        }
+        Ok(palette)
+    }
+}
+
+/// White on black, with plane two in orange as Octo shows it.
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette {
+            colours: [(0x00, 0x00, 0x00), (0xFF, 0xFF, 0xFF), (0xFF, 0x66, 0x00), (0x66, 0x22, 0x00)]
+        }
+    }
+}
+
+#[test]
+fn test_parse_palette() {
+    assert_eq!("amber".parse::<Palette>().unwrap(), Palette::AMBER);
+    let palette: Palette = "102030,FFEEDD".parse().unwrap();
+    assert_eq!(palette.colours[0], (0x10, 0x20, 0x30));
+    assert_eq!(palette.colours[1], (0xFF, 0xEE, 0xDD));
+    assert_eq!(palette.colours[2], Palette::default().colours[2]);
+    assert!("purple".parse::<Palette>().is_err());
+    assert!("fff,000".parse::<Palette>().is_err());
+}
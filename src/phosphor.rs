@@ -0,0 +1,89 @@
+//! Phosphor persistence: lit pixels fade out over a few frames rather
+//! than going dark at once, hiding the flicker of sprites being erased
+//! and redrawn.
+
+use machine::Chip8;
+use palette::{Colour, Palette};
+
+/// The brightness of each pixel, alongside `Chip8::gfx`.
+pub struct Phosphor {
+    frames: u32,            // Frames a pixel takes to fade out once turned off.
+    intensity: Vec<f32>,    // Each pixel's brightness, from 0 to 1.
+    lit: Vec<u8>            // The planes last lit at each pixel.
+}
+
+impl Phosphor {
+    /// Fades pixels out over `frames` frames. With 0 pixels go dark as
+    /// soon as they are turned off.
+    pub fn new(frames: u32) -> Phosphor {
+        Phosphor {
+            frames,
+            intensity: Vec::new(),
+            lit: Vec::new()
+        }
+    }
+
+    /// Advances one 60 Hz frame, lighting the pixels lit in `gfx` and
+    /// fading the rest. Returns whether the picture changed.
+    pub fn update(&mut self, c8: &Chip8) -> bool {
+        if self.lit.len() != c8.gfx.len() {
+            // The resolution changed, which clears the screen.
+            self.intensity = vec![0.0; c8.gfx.len()];
+            self.lit = vec![0; c8.gfx.len()];
+        }
+
+        let fade = 1.0 / (self.frames + 1) as f32;
+        let mut changed = false;
+        for ((intensity, lit), &pixel) in self.intensity.iter_mut().zip(self.lit.iter_mut()).zip(c8.gfx.iter()) {
+            let (before, planes) = (*intensity, *lit);
+            if pixel != 0 {
+                *intensity = 1.0;
+                *lit = pixel;
+            } else {
+                *intensity = (*intensity - fade).max(0.0);
+            }
+            changed |= *intensity != before || *lit != planes;
+        }
+        changed
+    }
+
+    /// The colour of each pixel in `palette`, blending the colour last lit
+    /// there into the background as it fades.
+    pub fn colours(&self, palette: &Palette) -> Vec<Colour> {
+        let background = palette.colour(0);
+        let blend = |from: u8, to: u8, amount: f32| (from as f32 + (to as f32 - from as f32) * amount).round() as u8;
+        self.intensity.iter().zip(self.lit.iter()).map(|(&intensity, &lit)| {
+            let colour = palette.colour(lit);
+            (blend(background.0, colour.0, intensity),
+             blend(background.1, colour.1, intensity),
+             blend(background.2, colour.2, intensity))
+        }).collect()
+    }
+}
+
+#[test]
+fn test_phosphor_fade() {
+    use platform::Platform;
+
+    let mut c8 = Chip8::new(Platform::CosmacVip);
+    let palette = Palette::default();
+    let mut phosphor = Phosphor::new(3);
+    c8.gfx[0] = 1;
+    assert!(phosphor.update(&c8));
+    assert_eq!(phosphor.colours(&palette)[0], (0xFF, 0xFF, 0xFF));
+
+    c8.gfx[0] = 0;
+    let mut shades = Vec::new();
+    while phosphor.update(&c8) {
+        shades.push(phosphor.colours(&palette)[0].0);
+    }
+    assert_eq!(shades, vec![0xBF, 0x80, 0x40, 0x00]);
+
+    // Without persistence pixels follow gfx exactly.
+    let mut phosphor = Phosphor::new(0);
+    c8.gfx[1] = 2;
+    phosphor.update(&c8);
+    c8.gfx[1] = 0;
+    phosphor.update(&c8);
+    assert_eq!(phosphor.colours(&palette)[1], palette.colour(0));
+}
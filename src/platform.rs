@@ -0,0 +1,127 @@
+use std::fmt;
+use std::str::FromStr;
+
+use instruction::InstructionSet;
+use quirks::Quirks;
+
+/// A historical CHIP-8 interpreter, bundling everything that has to be
+/// configured consistently to run programs written for it.
+///
+/// Defaults to the COSMAC VIP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Platform {
+    /// The original interpreter on the RCA COSMAC VIP (1977).
+    #[default]
+    CosmacVip,
+    /// CHIP-48 for the HP 48 calculators (1990).
+    Chip48,
+    /// SUPER-CHIP 1.0 for the HP 48 (1991).
+    SuperChip10,
+    /// SUPER-CHIP 1.1 for the HP 48 (1991).
+    SuperChip11,
+    /// John Earnest's XO-CHIP extensions, as implemented by Octo (2014).
+    XoChip,
+}
+
+impl Platform {
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Platform::CosmacVip => Quirks::cosmac_vip(),
+            // CHIP-48 advanced I by X rather than X + 1 on load/store. Programs
+            // that depend on that are rare, so it is treated as SUPER-CHIP.
+            Platform::Chip48 | Platform::SuperChip10 | Platform::SuperChip11 => Quirks::schip(),
+            Platform::XoChip => Quirks {
+                load_store_increments_i: true,
+                vf_reset:                false,
+                shift_uses_vy:           true,
+                jump_uses_vx:            false,
+                clip_sprites:            false,
+                display_wait:            false,
+            },
+        }
+    }
+
+    /// Bytes of addressable memory.
+    pub fn memory_size(self) -> usize {
+        match self {
+            Platform::XoChip => 0x10000,
+            _ => 0x1000
+        }
+    }
+
+    /// The largest display resolution the platform supports, in pixels.
+    pub fn resolution(self) -> (usize, usize) {
+        match self {
+            Platform::CosmacVip | Platform::Chip48 => (64, 32),
+            Platform::SuperChip10 | Platform::SuperChip11 | Platform::XoChip => (128, 64)
+        }
+    }
+
+    /// Instructions per second that programs for the platform expect.
+    pub fn default_ips(self) -> u32 {
+        match self {
+            Platform::CosmacVip => 660,
+            Platform::Chip48 | Platform::SuperChip10 | Platform::SuperChip11 => 1800,
+            Platform::XoChip => 30000
+        }
+    }
+
+    /// The name `FromStr` accepts for the platform.
+    pub fn name(self) -> &'static str {
+        match self {
+            Platform::CosmacVip   => "vip",
+            Platform::Chip48      => "chip48",
+            Platform::SuperChip10 => "schip1.0",
+            Platform::SuperChip11 => "schip1.1",
+            Platform::XoChip      => "xochip"
+        }
+    }
+
+    pub fn instruction_set(self) -> InstructionSet {
+        match self {
+            Platform::CosmacVip | Platform::Chip48 => InstructionSet::Chip8,
+            Platform::SuperChip10 => InstructionSet::SuperChip10,
+            Platform::SuperChip11 => InstructionSet::SuperChip11,
+            Platform::XoChip => InstructionSet::XoChip
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Platform::CosmacVip   => "COSMAC VIP",
+            Platform::Chip48      => "CHIP-48",
+            Platform::SuperChip10 => "SUPER-CHIP 1.0",
+            Platform::SuperChip11 => "SUPER-CHIP 1.1",
+            Platform::XoChip      => "XO-CHIP"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Parses the names accepted by `--platform`: `vip`, `chip48`, `schip1.0`,
+/// `schip1.1` (or just `schip`) and `xochip`.
+impl FromStr for Platform {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Platform, String> {
+        match name {
+            "vip"                => Ok(Platform::CosmacVip),
+            "chip48"             => Ok(Platform::Chip48),
+            "schip1.0"           => Ok(Platform::SuperChip10),
+            "schip" | "schip1.1" => Ok(Platform::SuperChip11),
+            "xochip"             => Ok(Platform::XoChip),
+            _ => Err(format!("Unknown platform: {}", name))
+        }
+    }
+}
+
+#[test]
+fn test_parse_platform() {
+    assert_eq!("vip".parse(), Ok(Platform::CosmacVip));
+    assert_eq!("schip".parse(), Ok(Platform::SuperChip11));
+    assert_eq!("schip1.0".parse(), Ok(Platform::SuperChip10));
+    assert!("chip8".parse::<Platform>().is_err());
+    assert_eq!(Platform::SuperChip10.name().parse(), Ok(Platform::SuperChip10));
+}
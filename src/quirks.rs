@@ -0,0 +1,112 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Behaviour that differs between historical CHIP-8 interpreters.
+///
+/// The default leaves every quirk off. Use `Quirks::cosmac_vip()` or
+/// `Quirks::schip()` for the behaviour of those interpreters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Quirks {
+    /// 8XY6/8XYE shift Vy and store the result in Vx, rather than
+    /// shifting Vx in place.
+    pub shift_uses_vy: bool,
+    /// FX55/FX65 leave I pointing one past the last register stored or loaded.
+    pub load_store_increments_i: bool,
+    /// 8XY1/8XY2/8XY3 reset Vf to 0.
+    pub vf_reset: bool,
+    /// BXNN jumps to XNN plus Vx instead of NNN plus V0.
+    pub jump_uses_vx: bool,
+    /// Sprites are clipped at the edges of the screen instead of
+    /// wrapping around to the other side.
+    pub clip_sprites: bool,
+    /// DXYN waits for the next 60 Hz frame, so at most one sprite is
+    /// drawn per frame.
+    pub display_wait: bool,
+}
+
+impl Quirks {
+    /// The original COSMAC VIP interpreter.
+    pub fn cosmac_vip() -> Quirks {
+        Quirks {
+            shift_uses_vy:           true,
+            load_store_increments_i: true,
+            vf_reset:                true,
+            jump_uses_vx:            false,
+            clip_sprites:            true,
+            display_wait:            true,
+        }
+    }
+
+    /// The SUPER-CHIP 1.1 interpreter for the HP 48.
+    pub fn schip() -> Quirks {
+        Quirks {
+            shift_uses_vy:           false,
+            load_store_increments_i: false,
+            vf_reset:                false,
+            jump_uses_vx:            true,
+            clip_sprites:            true,
+            display_wait:            false,
+        }
+    }
+}
+
+impl Quirks {
+    fn names(&self) -> [(&'static str, bool); 6] {
+        [
+            ("shift-uses-vy",           self.shift_uses_vy),
+            ("load-store-increments-i", self.load_store_increments_i),
+            ("vf-reset",                self.vf_reset),
+            ("jump-uses-vx",            self.jump_uses_vx),
+            ("clip-sprites",            self.clip_sprites),
+            ("display-wait",            self.display_wait),
+        ]
+    }
+}
+
+/// Formats the quirks that are on as a list `FromStr` accepts.
+impl fmt::Display for Quirks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let on: Vec<&str> = self.names().iter().filter(|&&(_, on)| on).map(|&(name, _)| name).collect();
+        write!(f, "{}", on.join(","))
+    }
+}
+
+/// Parses a comma separated list of quirk names, such as
+/// `shift-uses-vy,vf-reset`. Listed quirks are turned on and all
+/// others off. `vip` and `schip` select the preset for that interpreter
+/// and may be combined with further names.
+impl FromStr for Quirks {
+    type Err = String;
+
+    fn from_str(list: &str) -> Result<Quirks, String> {
+        let mut quirks = Quirks::default();
+
+        for name in list.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            match name {
+                "vip"                     => quirks = Quirks::cosmac_vip(),
+                "schip"                   => quirks = Quirks::schip(),
+                "shift-uses-vy"           => quirks.shift_uses_vy = true,
+                "load-store-increments-i" => quirks.load_store_increments_i = true,
+                "vf-reset"                => quirks.vf_reset = true,
+                "jump-uses-vx"            => quirks.jump_uses_vx = true,
+                "clip-sprites"            => quirks.clip_sprites = true,
+                "display-wait"            => quirks.display_wait = true,
+                _ => return Err(format!("Unknown quirk: {}", name))
+            }
+        }
+
+        Ok(quirks)
+    }
+}
+
+#[test]
+fn test_parse_quirks() {
+    assert_eq!("".parse(), Ok(Quirks::default()));
+    assert_eq!("vip".parse(), Ok(Quirks::cosmac_vip()));
+
+    let quirks: Quirks = "schip, display-wait".parse().unwrap();
+    assert!(quirks.jump_uses_vx && quirks.display_wait && !quirks.shift_uses_vy);
+
+    assert!("shift-uses-vx".parse::<Quirks>().is_err());
+    assert_eq!(Quirks::cosmac_vip().to_string().parse(), Ok(Quirks::cosmac_vip()));
+}
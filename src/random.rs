@@ -0,0 +1,200 @@
+//! Sources of random numbers for CXNN.
+
+use std::str::FromStr;
+
+/// A seedable source of random bytes.
+pub trait Random {
+    /// The next random byte, for CXNN to AND with NN.
+    fn next_byte(&mut self) -> u8;
+
+    /// Restarts the sequence from `seed`. Equal seeds give equal sequences.
+    fn reseed(&mut self, seed: u64);
+
+    /// Called once per 60 Hz timer tick, for generators that depend on
+    /// the passage of time.
+    fn tick(&mut self) {}
+
+    /// Which `Generator` this is.
+    fn generator(&self) -> Generator;
+}
+
+/// The available kinds of `Random`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Generator {
+    /// `Xorshift`, a fast general purpose generator.
+    #[default]
+    Xorshift,
+    /// `VipRandom`, the COSMAC VIP interpreter's routine.
+    Vip,
+}
+
+impl Generator {
+    /// Constructs a generator of this kind seeded with `seed`.
+    pub fn create(self, seed: u64) -> Box<dyn Random> {
+        match self {
+            Generator::Xorshift => Box::new(Xorshift::new(seed)),
+            Generator::Vip => Box::new(VipRandom::new(seed))
+        }
+    }
+
+    /// The name `FromStr` accepts for the generator.
+    pub fn name(self) -> &'static str {
+        match self {
+            Generator::Xorshift => "xorshift",
+            Generator::Vip => "vip"
+        }
+    }
+}
+
+impl FromStr for Generator {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Generator, String> {
+        match name {
+            "xorshift" => Ok(Generator::Xorshift),
+            "vip"      => Ok(Generator::Vip),
+            _ => Err(format!("Unknown random number generator: {}", name))
+        }
+    }
+}
+
+/// Marsaglia's xorshift64* generator.
+pub struct Xorshift {
+    state: u64      // Never zero.
+}
+
+impl Xorshift {
+    pub fn new(seed: u64) -> Xorshift {
+        let mut rng = Xorshift { state: 1 };
+        rng.reseed(seed);
+        rng
+    }
+}
+
+impl Random for Xorshift {
+    fn next_byte(&mut self) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+
+    fn reseed(&mut self, seed: u64) {
+        // Scramble the seed so that nearby seeds give unrelated
+        // sequences, and an all zero state cannot arise.
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        self.state = (z ^ (z >> 31)) | 1;
+    }
+
+    fn generator(&self) -> Generator {
+        Generator::Xorshift
+    }
+}
+
+/// The COSMAC VIP interpreter's CXNN routine.
+///
+/// The VIP kept a 16 bit counter in its R9 register, which the display
+/// interrupt incremented every frame and CXNN incremented again. CXNN
+/// added the byte at `0x100 + R9.0`, a location inside the interpreter's
+/// own code, to R9.1 and kept the sum in R9.1 as the random number. The
+/// result therefore depends on how many frames have passed as much as on
+/// how many numbers have been drawn.
+///
+/// The interpreter's code is not distributed with this crate, so `new`
+/// reads from a made up page of bytes instead. Its numbers depend on
+/// timing as a VIP's do, but are not the numbers a VIP would give. Load a
+/// dump of the real interpreter with `from_interpreter` for those.
+pub struct VipRandom {
+    r9:   u16,
+    page: [u8; 256]     // The bytes at 0x100 to 0x1FF.
+}
+
+impl VipRandom {
+    pub fn new(seed: u64) -> VipRandom {
+        let mut page = [0; 256];
+        let mut table = Xorshift::new(0x1802);
+        for byte in page.iter_mut() {
+            *byte = table.next_byte();
+        }
+        VipRandom::with_page(seed, page)
+    }
+
+    /// Constructs the generator reading from `page`, the contents of the
+    /// interpreter's second page of memory.
+    pub fn with_page(seed: u64, page: [u8; 256]) -> VipRandom {
+        VipRandom { r9: seed as u16, page }
+    }
+
+    /// Constructs the generator reading from `interpreter`, a dump of the
+    /// VIP's memory from 0x000 holding at least the 512 byte interpreter.
+    pub fn from_interpreter(seed: u64, interpreter: &[u8]) -> Result<VipRandom, String> {
+        if interpreter.len() < 0x200 {
+            return Err(format!("A VIP interpreter is 512 bytes, not {}", interpreter.len()));
+        }
+        let mut page = [0; 256];
+        page.copy_from_slice(&interpreter[0x100..0x200]);
+        Ok(VipRandom::with_page(seed, page))
+    }
+}
+
+impl Random for VipRandom {
+    fn next_byte(&mut self) -> u8 {
+        self.r9 = self.r9.wrapping_add(1);
+        let [high, low] = self.r9.to_be_bytes();
+        let high = high.wrapping_add(self.page[low as usize]);
+        self.r9 = u16::from_be_bytes([high, low]);
+        high
+    }
+
+    fn reseed(&mut self, seed: u64) {
+        self.r9 = seed as u16;
+    }
+
+    fn tick(&mut self) {
+        self.r9 = self.r9.wrapping_add(1);
+    }
+
+    fn generator(&self) -> Generator {
+        Generator::Vip
+    }
+}
+
+#[test]
+fn test_reseed_repeats() {
+    for &generator in [Generator::Xorshift, Generator::Vip].iter() {
+        let mut rng = generator.create(1234);
+        let first: Vec<u8> = (0..32).map(|_| rng.next_byte()).collect();
+        rng.reseed(1234);
+        let again: Vec<u8> = (0..32).map(|_| rng.next_byte()).collect();
+        assert_eq!(first, again);
+        assert!(first.iter().any(|&byte| byte != first[0]), "Not constant.");
+        assert_eq!(rng.generator(), generator);
+    }
+}
+
+#[test]
+fn test_vip_random() {
+    let mut page = [0; 256];
+    page[0x01] = 0x10;
+    page[0x03] = 0x05;
+    let mut rng = VipRandom::with_page(0x0000, page);
+
+    assert_eq!(rng.next_byte(), 0x10, "R9 = 0x0001, R9.1 += page[0x01].");
+    rng.tick();
+    assert_eq!(rng.next_byte(), 0x15, "R9 = 0x1003, R9.1 += page[0x03].");
+
+    let mut interpreter = vec![0; 0x200];
+    interpreter[0x101] = 0x10;
+    let mut rng = VipRandom::from_interpreter(0x0000, &interpreter).unwrap();
+    assert_eq!(rng.next_byte(), 0x10, "Reads the interpreter's second page.");
+    assert!(VipRandom::from_interpreter(0, &interpreter[..0x100]).is_err());
+}
+
+#[test]
+fn test_parse_generator() {
+    assert_eq!("vip".parse(), Ok(Generator::Vip));
+    assert_eq!(Generator::Xorshift.name().parse(), Ok(Generator::Xorshift));
+    assert!("mt19937".parse::<Generator>().is_err());
+}
@@ -0,0 +1,158 @@
+//! Rewinding, by keeping recent save states in a ring buffer.
+//!
+//! Only the newest snapshot is held in full. Each older one is stored as
+//! the runs of bytes in which it differs from the snapshot after it, so a
+//! few seconds of history costs little more than one save state.
+
+use std::collections::VecDeque;
+
+use machine::Chip8;
+use scheduler::TIMER_HZ;
+
+/// Changed runs closer together than this are stored as one run, which
+/// costs less than the bookkeeping for two.
+const RUN_GAP: usize = 8;
+
+/// The changes that turn one snapshot back into the one before it.
+struct Delta {
+    len:  usize,                    // Length of the older snapshot.
+    runs: Vec<(usize, Vec<u8>)>     // Offsets and bytes of the older snapshot that differ.
+}
+
+impl Delta {
+    fn new(newer: &[u8], older: &[u8]) -> Delta {
+        let mut runs: Vec<(usize, Vec<u8>)> = Vec::new();
+        for (offset, &byte) in older.iter().enumerate() {
+            if newer.get(offset) == Some(&byte) {
+                continue;
+            }
+            match runs.last_mut() {
+                Some(&mut (start, ref mut bytes)) if offset - (start + bytes.len()) < RUN_GAP => {
+                    bytes.extend_from_slice(&older[start + bytes.len()..=offset]);
+                },
+                _ => runs.push((offset, vec![byte]))
+            }
+        }
+        Delta { len: older.len(), runs }
+    }
+
+    fn apply(&self, snapshot: &mut Vec<u8>) {
+        snapshot.resize(self.len, 0);
+        for &(start, ref bytes) in self.runs.iter() {
+            snapshot[start..start + bytes.len()].copy_from_slice(bytes);
+        }
+    }
+}
+
+/// A ring buffer of the machine's recent history, one snapshot per frame.
+pub struct Rewind {
+    capacity: usize,                // Most frames that can be rewound.
+    latest:   Option<Vec<u8>>,      // The newest snapshot, in full.
+    deltas:   VecDeque<Delta>       // Older snapshots, oldest first.
+}
+
+impl Rewind {
+    /// Constructs a buffer holding up to `seconds` of frames.
+    pub fn new(seconds: u32) -> Rewind {
+        Rewind {
+            capacity: seconds.saturating_mul(TIMER_HZ) as usize,
+            latest:   None,
+            deltas:   VecDeque::new()
+        }
+    }
+
+    /// The number of frames that can currently be rewound.
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    /// Records the machine's state at the end of a frame, dropping the
+    /// oldest snapshot if the buffer is full.
+    pub fn push(&mut self, c8: &Chip8) {
+        if self.capacity == 0 {
+            return;
+        }
+        let snapshot = c8.state_bytes();
+        if let Some(older) = self.latest.take() {
+            self.deltas.push_back(Delta::new(&snapshot, &older));
+            if self.deltas.len() > self.capacity {
+                self.deltas.pop_front();
+            }
+        }
+        self.latest = Some(snapshot);
+    }
+
+    /// Steps the machine back one frame, keeping the keys currently held.
+    ///
+    /// Returns false, leaving the machine alone, once the oldest snapshot
+    /// has been reached.
+    pub fn rewind(&mut self, c8: &mut Chip8) -> bool {
+        let (delta, snapshot) = match (self.deltas.pop_back(), self.latest.as_mut()) {
+            (Some(delta), Some(snapshot)) => (delta, snapshot),
+            _ => return false
+        };
+        delta.apply(snapshot);
+
+        let key = c8.key;
+        c8.load_state_bytes(snapshot).expect("Rewind snapshot is corrupt.");
+        c8.key = key;
+        true
+    }
+
+    /// Forgets all history.
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+    }
+}
+
+#[test]
+fn test_rewind() {
+    use platform::Platform;
+
+    let mut c8 = Chip8::new(Platform::SuperChip11);
+    let mut rewind = Rewind::new(1);
+    for frame in 0..5 {
+        c8.v[0] = frame;
+        c8.memory[0x300 + frame as usize * 20] = frame;
+        if frame == 3 {
+            c8.execute_instruction(::instruction::Instruction::High).unwrap();
+        }
+        rewind.push(&c8);
+    }
+    assert_eq!(rewind.len(), 4);
+
+    c8.key[1] = 1;
+    for frame in (0..4).rev() {
+        assert!(rewind.rewind(&mut c8));
+        assert_eq!(c8.v[0], frame);
+        assert_eq!(c8.memory[0x300 + frame as usize * 20], frame);
+        assert_eq!(c8.memory[0x300 + (frame as usize + 1) * 20], 0);
+        assert_eq!(c8.hires, frame >= 3);
+        assert_eq!(c8.key[1], 1, "Held keys kept.");
+    }
+    assert!(!rewind.rewind(&mut c8), "Nothing older.");
+    assert_eq!(c8.v[0], 0);
+}
+
+#[test]
+fn test_rewind_capacity() {
+    use platform::Platform;
+
+    let mut c8 = Chip8::new(Platform::CosmacVip);
+    let mut rewind = Rewind::new(1);
+    for frame in 0..100 {
+        c8.i = frame;
+        rewind.push(&c8);
+    }
+    assert_eq!(rewind.len(), TIMER_HZ as usize);
+
+    while rewind.rewind(&mut c8) {}
+    assert_eq!(c8.i, 99 - TIMER_HZ as u16, "A full second back, and no further.");
+
+    assert_eq!(Rewind::new(u32::MAX).capacity, u32::MAX as usize, "Saturates.");
+}
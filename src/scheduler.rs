@@ -0,0 +1,110 @@
+use error::Chip8Error;
+use machine::{Chip8, StepOutcome};
+
+/// The rate at which the delay and sound timers count down.
+pub const TIMER_HZ: u32 = 60;
+
+/// Paces a `Chip8` in 60 Hz frames.
+///
+/// Each frame executes `ips / 60` instructions and then ticks the
+/// timers once. The remainder of the division is carried over to the
+/// next frame, so over one second exactly `ips` instructions run even
+/// when `ips` is not a multiple of 60.
+pub struct Scheduler {
+    ips:    u32,    // Instructions per second.
+    budget: u32     // Instruction budget carried over, in 1/60ths of an instruction.
+}
+
+impl Scheduler {
+    pub fn new(ips: u32) -> Scheduler {
+        Scheduler {
+            ips,
+            budget: 0
+        }
+    }
+
+    pub fn ips(&self) -> u32 {
+        self.ips
+    }
+
+    pub fn set_ips(&mut self, ips: u32) {
+        self.ips = ips;
+    }
+
+    /// Runs one 60 Hz frame: this frame's share of instructions followed
+    /// by a single timer tick.
+    ///
+    /// A program waiting on `FX0A` ends the frame's instructions early, as
+    /// the key state cannot change until the frontend polls input again.
+    /// So does a sprite drawn under the display wait quirk, or the program
+    /// exiting. The outcome that ended the frame is returned.
+    pub fn run_frame(&mut self, c8: &mut Chip8) -> Result<StepOutcome, Chip8Error> {
+        self.run_frame_until(c8, |_| false).map(|outcome| outcome.unwrap())
+    }
+
+    /// Runs one frame like `run_frame`, but calls `stop` before each
+    /// instruction. If it returns true the frame ends there, without
+    /// ticking the timers, and `None` is returned.
+    pub fn run_frame_until<F>(&mut self, c8: &mut Chip8, mut stop: F) -> Result<Option<StepOutcome>, Chip8Error>
+        where F: FnMut(&Chip8) -> bool
+    {
+        self.budget += self.ips;
+        let cycles = self.budget / TIMER_HZ;
+        self.budget %= TIMER_HZ;
+
+        let mut outcome = StepOutcome::Continue;
+        for _ in 0..cycles {
+            if stop(c8) {
+                return Ok(None);
+            }
+            outcome = c8.step()?;
+            if outcome != StepOutcome::Continue {
+                break;
+            }
+        }
+
+        c8.tick_timers();
+        Ok(Some(outcome))
+    }
+}
+
+#[test]
+fn test_run_frame_paces_instructions() {
+    use platform::Platform;
+
+    let mut c8 = Chip8::new(Platform::Chip48);
+    // Fill program memory with `7001`, add 1 to V0.
+    for pc in (0x200..0x1000).step_by(2) {
+        c8.memory[pc] = 0x70;
+        c8.memory[pc + 1] = 0x01;
+    }
+    c8.delay_timer = 100;
+
+    let mut scheduler = Scheduler::new(90);
+    scheduler.run_frame(&mut c8).unwrap();
+    assert_eq!(c8.v[0], 1, "Fractional instruction carried over.");
+    scheduler.run_frame(&mut c8).unwrap();
+    assert_eq!(c8.v[0], 3);
+
+    for _ in 2..60 {
+        scheduler.run_frame(&mut c8).unwrap();
+    }
+    assert_eq!(c8.v[0], 90, "Exactly ips instructions run per second.");
+    assert_eq!(c8.delay_timer, 40, "Timers tick once per frame.");
+}
+
+#[test]
+fn test_run_frame_stops_waiting_for_key() {
+    use platform::Platform;
+
+    let mut c8 = Chip8::new(Platform::Chip48);
+    c8.memory[0x200..0x204].copy_from_slice(&[0xF0, 0x0A, 0x12, 0x02]);
+
+    let mut scheduler = Scheduler::new(600);
+    scheduler.run_frame(&mut c8).unwrap();
+    assert_eq!(c8.pc, 0x200);
+
+    c8.key[0x7] = 1;
+    scheduler.run_frame(&mut c8).unwrap();
+    assert_eq!(c8.v[0], 0x7);
+}
@@ -0,0 +1,120 @@
+//! Pictures of the display, for tests and bug reports.
+
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use machine::Chip8;
+use palette::Palette;
+
+/// The characters `screen_text` uses for each combination of planes.
+const PIXEL_CHARS: [char; 4] = ['.', '#', '+', '@'];
+
+/// Draws `gfx` as text, one line per row: `.` for an unlit pixel, `#`
+/// for plane one, `+` for plane two and `@` for both.
+pub fn screen_text(c8: &Chip8) -> String {
+    let (width, _) = c8.display_size();
+    let mut out = String::with_capacity(c8.gfx.len() + c8.gfx.len() / width);
+    for row in c8.gfx.chunks(width) {
+        out.extend(row.iter().map(|&pixel| PIXEL_CHARS[(pixel & 0x3) as usize]));
+        out.push('\n');
+    }
+    out
+}
+
+/// Encodes `gfx` as a PNG image in `palette`, with each display pixel
+/// drawn as a `scale` by `scale` square.
+pub fn write_png<W: Write>(c8: &Chip8, palette: &Palette, scale: u32, writer: W) -> io::Result<()> {
+    let (width, _) = c8.display_size();
+    let scale = scale.max(1) as usize;
+    let mut rgb = Vec::with_capacity(c8.gfx.len() * scale * scale * 3);
+    for row in c8.gfx.chunks(width) {
+        let start = rgb.len();
+        for &pixel in row {
+            let (r, g, b) = palette.colour(pixel);
+            for _ in 0..scale {
+                rgb.extend_from_slice(&[r, g, b]);
+            }
+        }
+        for _ in 1..scale {
+            rgb.extend_from_within(start..start + width * scale * 3);
+        }
+    }
+
+    let (width, height) = c8.display_size();
+    let mut encoder = png::Encoder::new(writer, (width * scale) as u32, (height * scale) as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&rgb)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// The file a screenshot or recording of `rom` made at `time` is saved
+/// to: next to the ROM, named after it and the time in UTC, such as
+/// `games/pong-20240131-235959.png`.
+pub fn timestamped_path(rom: &str, extension: &str, time: SystemTime) -> String {
+    let rom = Path::new(rom);
+    let name = rom.file_stem().map_or("screenshot".into(), |stem| stem.to_string_lossy());
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+    let (year, month, day) = civil_date(secs / 86400);
+    let time = secs % 86400;
+    let file = format!("{}-{:04}{:02}{:02}-{:02}{:02}{:02}.{}",
+                       name, year, month, day, time / 3600, time / 60 % 60, time % 60, extension);
+    rom.with_file_name(file).to_string_lossy().into_owned()
+}
+
+/// The year, month and day `days` days after 1970-01-01, in the
+/// proleptic Gregorian calendar.
+fn civil_date(days: u64) -> (u64, u64, u64) {
+    // Count from 0000-03-01, so leap days fall at the end of each year.
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = era * 400 + year_of_era + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[test]
+fn test_screen_text() {
+    use platform::Platform;
+
+    let mut c8 = Chip8::new(Platform::CosmacVip);
+    c8.gfx[0] = 1;
+    c8.gfx[64 + 1] = 3;
+    let text = screen_text(&c8);
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 32);
+    assert!(lines[0].starts_with("#..."));
+    assert!(lines[1].starts_with(".@.."));
+    assert_eq!(lines[0].len(), 64);
+}
+
+#[test]
+fn test_write_png() {
+    use platform::Platform;
+
+    let c8 = Chip8::new(Platform::CosmacVip);
+    let mut out = Vec::new();
+    write_png(&c8, &Palette::default(), 4, &mut out).unwrap();
+    assert_eq!(&out[..8], b"\x89PNG\r\n\x1a\n");
+    // The IHDR chunk's width and height.
+    assert_eq!(&out[16..24], &[0, 0, 1, 0, 0, 0, 0, 128]);
+}
+
+#[test]
+fn test_timestamped_path() {
+    use std::time::Duration;
+
+    let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+    assert_eq!(timestamped_path("roms/pong.ch8", "png", at(0)), "roms/pong-19700101-000000.png");
+    assert_eq!(timestamped_path("pong", "png", at(951_782_400 + 3661)), "pong-20000229-010101.png");
+    assert_eq!(timestamped_path("a.ch8", "gif", at(1_706_745_599)), "a-20240131-235959.gif");
+}
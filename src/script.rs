@@ -0,0 +1,65 @@
+//! Scripted key presses, for running programs without a keyboard.
+
+use machine::Chip8;
+
+/// Key presses and releases at given frames.
+///
+/// Each line of a script is a frame number, a key from 0 to F and
+/// `down` or `up`, such as `120 5 down`. Blank lines and anything after
+/// a `#` are ignored.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputScript {
+    events: Vec<(u32, usize, bool)>     // Frame, key and whether it is pressed, in frame order.
+}
+
+impl InputScript {
+    pub fn parse(text: &str) -> Result<InputScript, String> {
+        let mut events = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("");
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.is_empty() {
+                continue;
+            }
+            let event = match fields[..] {
+                [frame, key, state] => {
+                    let frame = frame.parse().ok();
+                    let key = usize::from_str_radix(key, 16).ok().filter(|&key| key < 16);
+                    let state = match state { "down" => Some(true), "up" => Some(false), _ => None };
+                    frame.and_then(|frame| key.and_then(|key| state.map(|state| (frame, key, state))))
+                },
+                _ => None
+            };
+            match event {
+                Some(event) => events.push(event),
+                None => return Err(format!("line {}: expected FRAME KEY down|up", n + 1))
+            }
+        }
+        events.sort_by_key(|&(frame, _, _)| frame);
+        Ok(InputScript { events })
+    }
+
+    /// Presses and releases the keys scripted for `frame`.
+    pub fn apply(&self, frame: u32, c8: &mut Chip8) {
+        for &(_, key, down) in self.events.iter().filter(|&&(at, _, _)| at == frame) {
+            c8.key[key] = down as u8;
+        }
+    }
+}
+
+#[test]
+fn test_input_script() {
+    use platform::Platform;
+
+    let script = InputScript::parse("# Hold 5, then A.\n10 5 down\n\n20 5 up  # let go\n20 a down\n").unwrap();
+    let mut c8 = Chip8::new(Platform::CosmacVip);
+    script.apply(9, &mut c8);
+    assert_eq!(c8.key[5], 0);
+    script.apply(10, &mut c8);
+    assert_eq!(c8.key[5], 1);
+    script.apply(20, &mut c8);
+    assert_eq!((c8.key[5], c8.key[0xA]), (0, 1));
+
+    assert_eq!(InputScript::parse("10 g down").unwrap_err(), "line 1: expected FRAME KEY down|up");
+    assert!(InputScript::parse("10 5").is_err());
+}
@@ -0,0 +1,300 @@
+//! Save states.
+//!
+//! A state is the magic bytes `C8ST`, a little endian u16 format version,
+//! the machine's fields and finally a u64 FNV-1a checksum of everything
+//! before it. Multi-byte values are little endian and variable length
+//! fields are prefixed with their length as a u32.
+
+use std::io;
+use std::io::prelude::*;
+
+use hash::fnv1a;
+use machine::Chip8;
+use platform::Platform;
+use quirks::Quirks;
+
+const MAGIC: &[u8; 4] = b"C8ST";
+
+/// The current format version. Bump it whenever the layout changes.
+pub const STATE_VERSION: u16 = 1;
+
+const PLATFORMS: [Platform; 5] = [
+    Platform::CosmacVip,
+    Platform::Chip48,
+    Platform::SuperChip10,
+    Platform::SuperChip11,
+    Platform::XoChip
+];
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid save state: {}", message))
+}
+
+fn quirks_to_bits(quirks: &Quirks) -> u8 {
+    [quirks.shift_uses_vy, quirks.load_store_increments_i, quirks.vf_reset,
+     quirks.jump_uses_vx, quirks.clip_sprites, quirks.display_wait]
+        .iter()
+        .enumerate()
+        .fold(0, |bits, (n, &on)| bits | (on as u8) << n)
+}
+
+fn quirks_from_bits(bits: u8) -> Quirks {
+    let on = |n: u8| bits & (1 << n) != 0;
+    Quirks {
+        shift_uses_vy:           on(0),
+        load_store_increments_i: on(1),
+        vf_reset:                on(2),
+        jump_uses_vx:            on(3),
+        clip_sprites:            on(4),
+        display_wait:            on(5),
+    }
+}
+
+/// Reads fields in order from a state's payload.
+struct Fields<'a> {
+    data: &'a [u8],
+    pos:  usize
+}
+
+impl<'a> Fields<'a> {
+    fn bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.data.len() - self.pos < len {
+            return Err(invalid("truncated"));
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        let bytes = self.bytes(2)?;
+        Ok(bytes[0] as u16 | (bytes[1] as u16) << 8)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let bytes = self.bytes(4)?;
+        Ok((0..4).fold(0, |value, n| value | (bytes[n] as u32) << (8 * n)))
+    }
+
+    fn array16(&mut self) -> io::Result<[u8; 16]> {
+        let mut array = [0; 16];
+        array.copy_from_slice(self.bytes(16)?);
+        Ok(array)
+    }
+
+    fn vec(&mut self) -> io::Result<Vec<u8>> {
+        let len = self.u32()? as usize;
+        Ok(self.bytes(len)?.to_vec())
+    }
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&[value as u8, (value >> 8) as u8]);
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend((0..4).map(|n| (value >> (8 * n)) as u8));
+}
+
+fn put_vec(out: &mut Vec<u8>, data: &[u8]) {
+    put_u32(out, data.len() as u32);
+    out.extend_from_slice(data);
+}
+
+impl Chip8 {
+    /// Serialises the machine to bytes in the save state format.
+    pub fn state_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.memory.len() + self.gfx.len() + 256);
+        out.extend_from_slice(MAGIC);
+        put_u16(&mut out, STATE_VERSION);
+
+        let platform = PLATFORMS.iter().position(|&p| p == self.platform).unwrap();
+        out.push(platform as u8);
+        out.push(quirks_to_bits(&self.quirks));
+        put_u16(&mut out, self.opcode);
+        put_vec(&mut out, &self.memory);
+        out.extend_from_slice(&self.v);
+        put_u16(&mut out, self.i);
+        put_u16(&mut out, self.pc);
+        out.push(self.hires as u8);
+        put_vec(&mut out, &self.gfx);
+        out.push(self.planes);
+        out.push(self.delay_timer);
+        out.push(self.sound_timer);
+        out.extend_from_slice(&self.audio_pattern);
+        out.push(self.pitch);
+        for &address in self.stack.iter() {
+            put_u16(&mut out, address);
+        }
+        put_u16(&mut out, self.sp);
+        out.extend_from_slice(&self.key);
+        out.extend_from_slice(&self.rpl);
+
+        let checksum = fnv1a(&out);
+        out.extend((0..8).map(|n| (checksum >> (8 * n)) as u8));
+        out
+    }
+
+    /// Restores the machine from bytes produced by `state_bytes`.
+    ///
+    /// The state is checked in full before anything is changed, so on
+    /// error the machine is left as it was.
+    pub fn load_state_bytes(&mut self, data: &[u8]) -> io::Result<()> {
+        if data.len() < MAGIC.len() + 2 + 8 || &data[..MAGIC.len()] != MAGIC {
+            return Err(invalid("not a save state"));
+        }
+        let (payload, checksum) = data.split_at(data.len() - 8);
+        let checksum = (0..8).fold(0, |value, n| value | (checksum[n] as u64) << (8 * n));
+        if fnv1a(payload) != checksum {
+            return Err(invalid("checksum mismatch"));
+        }
+
+        let mut fields = Fields { data: payload, pos: MAGIC.len() };
+        let version = fields.u16()?;
+        if version != STATE_VERSION {
+            return Err(invalid(&format!("unsupported version {}", version)));
+        }
+
+        let platform = *PLATFORMS.get(fields.u8()? as usize).ok_or_else(|| invalid("unknown platform"))?;
+        let mut c8 = Chip8::new(platform);
+        c8.quirks = quirks_from_bits(fields.u8()?);
+        c8.opcode = fields.u16()?;
+        c8.memory = fields.vec()?;
+        c8.v = fields.array16()?;
+        c8.i = fields.u16()?;
+        c8.pc = fields.u16()?;
+        c8.hires = fields.u8()? != 0;
+        c8.gfx = fields.vec()?;
+        c8.planes = fields.u8()?;
+        c8.delay_timer = fields.u8()?;
+        let sound_timer = fields.u8()?;
+        c8.audio_pattern = fields.array16()?;
+        c8.pitch = fields.u8()?;
+        for address in c8.stack.iter_mut() {
+            *address = fields.u16()?;
+        }
+        c8.sp = fields.u16()?;
+        c8.key = fields.array16()?;
+        c8.rpl = fields.array16()?;
+
+        let (width, height) = c8.display_size();
+        if c8.memory.len() != platform.memory_size() || c8.gfx.len() != width * height {
+            return Err(invalid("wrong memory or display size"));
+        }
+        // I may be left just past the end by a store that increments it.
+        if c8.pc as usize >= c8.memory.len() || c8.i as usize > c8.memory.len() {
+            return Err(invalid("address out of memory"));
+        }
+        if c8.sp as usize > c8.stack.len() {
+            return Err(invalid("stack pointer out of range"));
+        }
+        if fields.pos != payload.len() {
+            return Err(invalid("trailing data"));
+        }
+
+        // Keep the frontend's view of the buzzer in step with the new timer.
+        c8.sound_timer = self.sound_timer;
+        c8.set_sound_timer(sound_timer);
+        c8.sound_events.splice(0..0, self.sound_events.drain(..));
+        c8.draw_flag = true;
+        // The random number generator is not part of the state.
+        std::mem::swap(&mut c8.rng, &mut self.rng);
+        *self = c8;
+        Ok(())
+    }
+
+    /// Writes the machine to `writer` in the save state format.
+    pub fn save_state<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.state_bytes())
+    }
+
+    /// Restores the machine from a save state read from `reader`.
+    pub fn load_state<R: Read>(&mut self, reader: &mut R) -> io::Result<()> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        self.load_state_bytes(&data)
+    }
+}
+
+#[test]
+fn test_state_round_trip() {
+    let mut c8 = Chip8::new(Platform::SuperChip11);
+    c8.quirks.display_wait = true;
+    c8.memory[0x200] = 0x12;
+    c8.v[3] = 0x45;
+    c8.i = 0x300;
+    c8.pc = 0x246;
+    c8.set_hires(true);
+    c8.gfx[100] = 1;
+    c8.delay_timer = 7;
+    c8.stack[0] = 0x222;
+    c8.sp = 1;
+    c8.key[0xA] = 1;
+    c8.rpl[2] = 9;
+
+    let mut saved = Vec::new();
+    c8.save_state(&mut saved).unwrap();
+
+    let mut loaded = Chip8::new(Platform::CosmacVip);
+    loaded.load_state(&mut &saved[..]).unwrap();
+    assert_eq!(loaded.platform, Platform::SuperChip11);
+    assert_eq!(loaded.quirks, c8.quirks);
+    assert_eq!(loaded.memory, c8.memory);
+    assert_eq!((loaded.v, loaded.i, loaded.pc), (c8.v, c8.i, c8.pc));
+    assert!(loaded.hires);
+    assert_eq!(loaded.gfx, c8.gfx);
+    assert_eq!(loaded.delay_timer, 7);
+    assert_eq!((loaded.stack, loaded.sp), (c8.stack, c8.sp));
+    assert_eq!(loaded.key, c8.key);
+    assert_eq!(loaded.rpl, c8.rpl);
+    assert_eq!(loaded.state_bytes(), saved);
+}
+
+#[test]
+fn test_state_rejects_corruption() {
+    let mut c8 = Chip8::new(Platform::CosmacVip);
+    c8.v[0] = 1;
+    let saved = c8.state_bytes();
+
+    let mut corrupt = saved.clone();
+    corrupt[100] ^= 0xFF;
+    assert!(c8.load_state_bytes(&corrupt).is_err(), "Checksum catches corruption.");
+
+    assert!(c8.load_state_bytes(&saved[..saved.len() - 1]).is_err(), "Truncated.");
+    assert!(c8.load_state_bytes(b"not a save state").is_err(), "Bad magic.");
+
+    let mut future = saved.clone();
+    future[4] = 2;
+    let len = future.len() - 8;
+    let checksum = fnv1a(&future[..len]);
+    for n in 0..8 {
+        future[len + n] = (checksum >> (8 * n)) as u8;
+    }
+    assert!(c8.load_state_bytes(&future).is_err(), "Unknown version.");
+
+    let mut bad = Chip8::new(Platform::CosmacVip);
+    bad.sp = 17;
+    assert!(c8.load_state_bytes(&bad.state_bytes()).is_err(), "Stack pointer past the stack.");
+    bad.sp = 0;
+    bad.pc = 0x1000;
+    assert!(c8.load_state_bytes(&bad.state_bytes()).is_err(), "Program counter past memory.");
+    bad.pc = 0x200;
+    bad.i = 0x1001;
+    assert!(c8.load_state_bytes(&bad.state_bytes()).is_err(), "I past memory.");
+    assert_eq!(c8.v[0], 1, "Unchanged by failed loads.");
+}
+
+#[test]
+fn test_state_sound_events() {
+    let mut c8 = Chip8::new(Platform::CosmacVip);
+    c8.sound_timer = 5;
+    let saved = c8.state_bytes();
+
+    let mut silent = Chip8::new(Platform::CosmacVip);
+    silent.load_state_bytes(&saved).unwrap();
+    assert_eq!(silent.take_sound_events(), vec![::machine::SoundEvent::Start]);
+}